@@ -0,0 +1,77 @@
+use crate::evaluate::*;
+use crate::position::*;
+use crate::search::*;
+use crate::sfen::START_SFEN;
+use crate::thread::*;
+use crate::tt::*;
+use crate::usioption::*;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+const BENCH_SFENS: [&str; 4] = [
+    START_SFEN,
+    "lnsgkgsnl/1r5b1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL b - 3",
+    "ln1g3nl/1r1sk1gb1/p1pppp1pp/6p2/1p7/2P1P1P2/PPBP1P1PP/2G1R1SK1/LNS2G1NL b S 1",
+    "l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w RGgsn5p 1",
+];
+
+pub struct BenchResult {
+    pub total_nodes: i64,
+    pub elapsed_ms: u64,
+    pub nps: u64,
+}
+
+// Search each position of BENCH_SFENS to the depth with 1 thread.
+// The evaluation files must be loaded in advance.
+// The total nodes only depend on the depth, because the search has no time limit and starts from the cleared tables.
+pub fn bench(depth: u32) -> BenchResult {
+    let mut thread_pool = ThreadPool::new();
+    let usi_options = UsiOptions::new();
+    let mut tt = TranspositionTable::new();
+    let mut ehash = EvalHash::new();
+    tt.resize(16, &mut thread_pool);
+    ehash.resize(16, &mut thread_pool);
+    thread_pool.set(1, &mut tt, &mut ehash);
+    let start = std::time::Instant::now();
+    let mut total_nodes = 0;
+    for sfen in BENCH_SFENS.iter() {
+        let pos = Position::new_from_sfen(sfen).unwrap();
+        tt.clear();
+        ehash.clear();
+        thread_pool.clear();
+        let limits = {
+            let mut limits = LimitsType::new();
+            limits.depth = Some(depth);
+            limits.start_time = Some(std::time::Instant::now());
+            limits
+        };
+        let stop = Arc::new(AtomicBool::new(false));
+        thread_pool.search_with_stop(&pos, &mut tt, limits, &usi_options, stop);
+        total_nodes += thread_pool.nodes_searched();
+    }
+    let elapsed_ms = start.elapsed().as_millis() as u64;
+    BenchResult {
+        total_nodes,
+        elapsed_ms,
+        nps: (total_nodes as u64 * 1000)
+            .checked_div(elapsed_ms)
+            .unwrap_or(0),
+    }
+}
+
+#[test]
+fn test_bench() {
+    const STACK_SIZE: usize = 128 * 1024 * 1024;
+    std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(|| {
+            load_evaluate_files(&UsiOptions::new().get_string("Eval_Dir")).unwrap();
+            let first = bench(4);
+            let second = bench(4);
+            assert!(first.total_nodes > 0);
+            assert_eq!(first.total_nodes, second.total_nodes);
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
@@ -410,6 +410,16 @@ impl Bitboard {
             _ => unreachable!(),
         }
     }
+    // The three ranks where the pieces of c can promote. Same as opponent_field_mask(c).
+    #[allow(dead_code)]
+    pub fn promotion_zone(c: Color) -> Bitboard {
+        Bitboard::opponent_field_mask(c)
+    }
+    // The three ranks nearest to c. This is the promotion zone of the opponent.
+    #[allow(dead_code)]
+    pub fn own_camp(c: Color) -> Bitboard {
+        Bitboard::opponent_field_mask(c.inverse())
+    }
     #[allow(dead_code)]
     pub fn in_front_mask(c: Color, r: Rank) -> Bitboard {
         debug_assert!(0 <= c.0 && c.0 < Color::NUM as i32);
@@ -420,6 +430,48 @@ impl Bitboard {
                 .get_unchecked(c.0 as usize)
         }
     }
+    // Every square moved one step to dir. Squares moved off the board are dropped and never wrap to another file or rank.
+    #[allow(dead_code)]
+    pub fn shift(self, dir: Direction) -> Bitboard {
+        match dir {
+            Direction::N => self.shift_n(),
+            Direction::NE => self.shift_n().shift_e(),
+            Direction::E => self.shift_e(),
+            Direction::SE => self.shift_s().shift_e(),
+            Direction::S => self.shift_s(),
+            Direction::SW => self.shift_s().shift_w(),
+            Direction::W => self.shift_w(),
+            Direction::NW => self.shift_n().shift_w(),
+        }
+    }
+    fn shift_n(self) -> Bitboard {
+        debug_assert_eq!(Square::DELTA_N.0, -1);
+        (self & !Bitboard::RANK1_MASK) >> 1
+    }
+    fn shift_s(self) -> Bitboard {
+        debug_assert_eq!(Square::DELTA_S.0, 1);
+        (self & !Bitboard::RANK9_MASK) << 1
+    }
+    // File 7 is carried from v[0] into v[1].
+    fn shift_w(self) -> Bitboard {
+        debug_assert_eq!(Square::DELTA_W.0, 9);
+        Bitboard {
+            v: [
+                (self.v[0] << 9) & Bitboard::ALL.v[0],
+                ((self.v[1] << 9) | (self.v[0] >> (9 * 6))) & Bitboard::ALL.v[1],
+            ],
+        }
+    }
+    // File 8 is carried from v[1] into v[0].
+    fn shift_e(self) -> Bitboard {
+        debug_assert_eq!(Square::DELTA_E.0, -9);
+        Bitboard {
+            v: [
+                (self.v[0] >> 9) | ((self.v[1] & 0x1ff) << (9 * 6)),
+                self.v[1] >> 9,
+            ],
+        }
+    }
     pub fn between_mask(sq0: Square, sq1: Square) -> Bitboard {
         debug_assert!(0 <= sq0.0 && sq0.0 < Square::NUM as i32);
         debug_assert!(0 <= sq1.0 && sq1.0 < Square::NUM as i32);
@@ -440,6 +492,20 @@ impl Bitboard {
     }
 }
 
+// Directions for Bitboard::shift(). Same orientation as Square::DELTA_N etc.
+#[allow(dead_code)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Direction {
+    N,
+    NE,
+    E,
+    SE,
+    S,
+    SW,
+    W,
+    NW,
+}
+
 impl Iterator for Bitboard {
     type Item = Square;
     fn next(&mut self) -> Option<Self::Item> {
@@ -1108,6 +1174,27 @@ fn test_opponent_field_mask() {
     }
 }
 
+#[test]
+fn test_promotion_zone_and_own_camp() {
+    let ranks = |rs: &[Rank]| {
+        rs.iter()
+            .fold(Bitboard::ZERO, |bb, &r| bb | Bitboard::rank_mask(r))
+    };
+    let ranks123 = ranks(&[Rank::RANK1, Rank::RANK2, Rank::RANK3]);
+    let ranks789 = ranks(&[Rank::RANK7, Rank::RANK8, Rank::RANK9]);
+    assert_eq!(Bitboard::promotion_zone(Color::BLACK), ranks123);
+    assert_eq!(Bitboard::promotion_zone(Color::BLACK).count_ones(), 27);
+    assert_eq!(Bitboard::promotion_zone(Color::WHITE), ranks789);
+    assert_eq!(Bitboard::own_camp(Color::BLACK), ranks789);
+    assert_eq!(Bitboard::own_camp(Color::WHITE), ranks123);
+    for &r in [Rank::RANK1, Rank::RANK2, Rank::RANK3].iter() {
+        assert_eq!(
+            (Bitboard::promotion_zone(Color::BLACK) & Bitboard::rank_mask(r)).count_ones(),
+            9
+        );
+    }
+}
+
 #[test]
 fn test_block_bits() {
     let v = vec![
@@ -1358,3 +1445,56 @@ fn test_proximity_check_mask() {
         .join()
         .unwrap();
 }
+
+#[test]
+fn test_bitboard_shift() {
+    let bb = Bitboard::square_mask(Square::SQ55).shift(Direction::N);
+    assert_eq!(bb.count_ones(), 1);
+    assert!(bb.is_set(Square::SQ54));
+
+    let dirs = [
+        (Direction::N, Square::DELTA_N),
+        (Direction::NE, Square::DELTA_NE),
+        (Direction::E, Square::DELTA_E),
+        (Direction::SE, Square::DELTA_SE),
+        (Direction::S, Square::DELTA_S),
+        (Direction::SW, Square::DELTA_SW),
+        (Direction::W, Square::DELTA_W),
+        (Direction::NW, Square::DELTA_NW),
+    ];
+    for &sq in Square::ALL.iter() {
+        for &(dir, delta) in dirs.iter() {
+            let bb = Bitboard::square_mask(sq).shift(dir);
+            let to = sq.add_unchecked(delta);
+            let on_board = to.is_ok()
+                && (File::new(sq).0 - File::new(to).0).abs() <= 1
+                && (Rank::new(sq).0 - Rank::new(to).0).abs() <= 1;
+            if on_board {
+                assert_eq!(bb, Bitboard::square_mask(to));
+            } else {
+                assert_eq!(bb, Bitboard::ZERO);
+            }
+        }
+    }
+
+    assert_eq!(
+        Bitboard::file_mask(File::FILE7).shift(Direction::W),
+        Bitboard::file_mask(File::FILE8)
+    );
+    assert_eq!(
+        Bitboard::file_mask(File::FILE8).shift(Direction::E),
+        Bitboard::file_mask(File::FILE7)
+    );
+    assert_eq!(
+        Bitboard::file_mask(File::FILE9).shift(Direction::W),
+        Bitboard::ZERO
+    );
+    assert_eq!(
+        Bitboard::rank_mask(Rank::RANK1).shift(Direction::N),
+        Bitboard::ZERO
+    );
+    assert_eq!(
+        Bitboard::ALL.shift(Direction::S),
+        Bitboard::ALL & !Bitboard::rank_mask(Rank::RANK1)
+    );
+}
@@ -223,59 +223,100 @@ lazy_static! {
     };
 }
 
+#[derive(Debug, PartialEq, Eq)]
+pub enum EvalLoadError {
+    SizeMismatch {
+        name: String,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl std::fmt::Display for EvalLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EvalLoadError::SizeMismatch {
+                name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Error: {} size mismatch. expected: {} bytes, actual: {} bytes.",
+                name, expected, actual
+            ),
+        }
+    }
+}
+
 pub struct Evaluator {
     pub kpp: *const [[[[i16; 2]; EvalIndex::FE_END.0]; EvalIndex::FE_END.0]; Square::NUM],
     pub kkp: *const [[[[i16; 2]; EvalIndex::FE_END.0]; Square::NUM]; Square::NUM],
 }
 
 impl Evaluator {
-    fn load_kpp(&mut self, path: &str) -> std::io::Result<()> {
+    const KPP_BYTES: usize = 2 * 2 * EvalIndex::FE_END.0 * EvalIndex::FE_END.0 * Square::NUM;
+    const KKP_BYTES: usize = 2 * 2 * EvalIndex::FE_END.0 * Square::NUM * Square::NUM;
+
+    fn check_size(name: &str, expected: usize, actual: usize) -> Result<(), EvalLoadError> {
+        if expected != actual {
+            return Err(EvalLoadError::SizeMismatch {
+                name: name.to_string(),
+                expected,
+                actual,
+            });
+        }
+        Ok(())
+    }
+    fn check_file_size(path: &std::path::Path, expected: usize) -> std::io::Result<()> {
+        let actual = std::fs::metadata(path)?.len() as usize;
+        Evaluator::check_size(&path.display().to_string(), expected, actual)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err.to_string()))
+    }
+    fn load_kpp(&mut self, path: &std::path::Path) -> std::io::Result<()> {
+        Evaluator::check_file_size(path, Evaluator::KPP_BYTES)?;
         let mut file = std::fs::File::open(path)?;
         let ptr = BUFFER_KPP.lock().unwrap().as_mut_ptr() as *mut u8;
-        let slice = unsafe {
-            std::slice::from_raw_parts_mut(
-                ptr,
-                2 * 2 * EvalIndex::FE_END.0 * EvalIndex::FE_END.0 * Square::NUM,
-            )
-        };
+        let slice = unsafe { std::slice::from_raw_parts_mut(ptr, Evaluator::KPP_BYTES) };
         file.read_exact(slice)?;
         self.kpp = BUFFER_KPP.lock().unwrap().as_mut_ptr()
             as *mut [[[[i16; 2]; EvalIndex::FE_END.0]; EvalIndex::FE_END.0]; Square::NUM];
         Ok(())
     }
-    fn load_kkp(&mut self, path: &str) -> std::io::Result<()> {
+    fn load_kkp(&mut self, path: &std::path::Path) -> std::io::Result<()> {
+        Evaluator::check_file_size(path, Evaluator::KKP_BYTES)?;
         let mut file = std::fs::File::open(path)?;
         let ptr = BUFFER_KKP.lock().unwrap().as_mut_ptr() as *mut u8;
-        let slice = unsafe {
-            std::slice::from_raw_parts_mut(
-                ptr,
-                2 * 2 * EvalIndex::FE_END.0 * Square::NUM * Square::NUM,
-            )
-        };
+        let slice = unsafe { std::slice::from_raw_parts_mut(ptr, Evaluator::KKP_BYTES) };
         file.read_exact(slice)?;
         self.kkp = BUFFER_KKP.lock().unwrap().as_mut_ptr()
             as *mut [[[[i16; 2]; EvalIndex::FE_END.0]; Square::NUM]; Square::NUM];
         Ok(())
     }
+    fn load_from_bytes(&mut self, kpp: &[u8], kkp: &[u8]) -> Result<(), EvalLoadError> {
+        // Check both sizes before overwriting the current tables.
+        Evaluator::check_size("KPP", Evaluator::KPP_BYTES, kpp.len())?;
+        Evaluator::check_size("KKP", Evaluator::KKP_BYTES, kkp.len())?;
+        let ptr = BUFFER_KPP.lock().unwrap().as_mut_ptr() as *mut u8;
+        unsafe { std::slice::from_raw_parts_mut(ptr, Evaluator::KPP_BYTES) }.copy_from_slice(kpp);
+        self.kpp = BUFFER_KPP.lock().unwrap().as_mut_ptr()
+            as *mut [[[[i16; 2]; EvalIndex::FE_END.0]; EvalIndex::FE_END.0]; Square::NUM];
+        let ptr = BUFFER_KKP.lock().unwrap().as_mut_ptr() as *mut u8;
+        unsafe { std::slice::from_raw_parts_mut(ptr, Evaluator::KKP_BYTES) }.copy_from_slice(kkp);
+        self.kkp = BUFFER_KKP.lock().unwrap().as_mut_ptr()
+            as *mut [[[[i16; 2]; EvalIndex::FE_END.0]; Square::NUM]; Square::NUM];
+        Ok(())
+    }
     fn write_kpp(&mut self, path: &str) -> std::io::Result<()> {
         let mut file = std::fs::File::create(path)?;
-        let slice: &[u8] = unsafe {
-            std::slice::from_raw_parts(
-                self.kpp as *const u8,
-                2 * 2 * EvalIndex::FE_END.0 * EvalIndex::FE_END.0 * Square::NUM,
-            )
-        };
+        let slice: &[u8] =
+            unsafe { std::slice::from_raw_parts(self.kpp as *const u8, Evaluator::KPP_BYTES) };
         file.write_all(slice)?;
         Ok(())
     }
     fn write_kkp(&mut self, path: &str) -> std::io::Result<()> {
         let mut file = std::fs::File::create(path)?;
-        let slice: &[u8] = unsafe {
-            std::slice::from_raw_parts(
-                self.kkp as *const u8,
-                2 * 2 * EvalIndex::FE_END.0 * Square::NUM * Square::NUM,
-            )
-        };
+        let slice: &[u8] =
+            unsafe { std::slice::from_raw_parts(self.kkp as *const u8, Evaluator::KKP_BYTES) };
         file.write_all(slice)?;
         Ok(())
     }
@@ -621,28 +662,71 @@ pub static mut EVALUATOR: Evaluator = Evaluator {
 };
 
 pub fn load_evaluate_files(eval_dir: &str) -> Result<(), String> {
-    let kpp_file_name = {
-        let mut path = std::path::PathBuf::from(eval_dir);
-        path.push("KPP.bin");
-        path.as_path().as_os_str().to_str().unwrap().to_string()
-    };
-    if let Err(err) = unsafe { EVALUATOR.load_kpp(&kpp_file_name) } {
-        return Err(format!("{}\nFile name: {}", err, kpp_file_name));
+    // NNUE is used instead of KPP and KKP if it exists. Then KPP.bin and KKP.bin aren't needed.
+    #[cfg(feature = "nnue")]
+    {
+        let nnue_file_name = {
+            let mut path = std::path::PathBuf::from(eval_dir);
+            path.push("nn.bin");
+            path.as_path().as_os_str().to_str().unwrap().to_string()
+        };
+        if std::path::Path::new(&nnue_file_name).exists() {
+            if let Err(err) = crate::nnue::load_nnue_file(&nnue_file_name) {
+                return Err(format!("{}\nFile name: {}", err, nnue_file_name));
+            }
+            return Ok(());
+        }
     }
-    let kkp_file_name = {
-        let mut path = std::path::PathBuf::from(eval_dir);
-        path.push("KKP.bin");
-        path.as_path().as_os_str().to_str().unwrap().to_string()
-    };
-    if let Err(err) = unsafe { EVALUATOR.load_kkp(&kkp_file_name) } {
-        return Err(format!("{}\nFile name: {}", err, kkp_file_name));
+    if let Err(err) = load_eval_from_path(std::path::Path::new(eval_dir)) {
+        return Err(err.to_string());
     }
     Ok(())
 }
 
+// Loads KPP.bin and KKP.bin in the directory "path". The errors have the file name.
+pub fn load_eval_from_path(path: &std::path::Path) -> std::io::Result<()> {
+    let with_file_name = |err: std::io::Error, file_name: &std::path::Path| {
+        std::io::Error::new(
+            err.kind(),
+            format!("{}\nFile name: {}", err, file_name.display()),
+        )
+    };
+    let evaluator = unsafe { &mut *std::ptr::addr_of_mut!(EVALUATOR) };
+    let kpp_file_name = path.join("KPP.bin");
+    evaluator
+        .load_kpp(&kpp_file_name)
+        .map_err(|err| with_file_name(err, &kpp_file_name))?;
+    let kkp_file_name = path.join("KKP.bin");
+    evaluator
+        .load_kkp(&kkp_file_name)
+        .map_err(|err| with_file_name(err, &kkp_file_name))
+}
+
+// "bytes" is KPP.bin followed by KKP.bin. With the "nnue" feature, it can be nn.bin, which has another size.
+#[allow(dead_code)]
+pub fn load_eval_from_bytes(bytes: &[u8]) -> Result<(), EvalLoadError> {
+    #[cfg(feature = "nnue")]
+    {
+        if bytes.len() == crate::nnue::Network::BYTES {
+            return crate::nnue::load_nnue_from_bytes(bytes);
+        }
+    }
+    Evaluator::check_size(
+        "KPP and KKP",
+        Evaluator::KPP_BYTES + Evaluator::KKP_BYTES,
+        bytes.len(),
+    )?;
+    let (kpp, kkp) = bytes.split_at(Evaluator::KPP_BYTES);
+    unsafe { (*std::ptr::addr_of_mut!(EVALUATOR)).load_from_bytes(kpp, kkp) }
+}
+
 pub fn write_evaluate_files() -> Result<(), String> {
     let kpp_file_name = "KPP.bin";
     let kkp_file_name = "KKP.bin";
+    // Only NNUE may be loaded.
+    if unsafe { EVALUATOR.kpp.is_null() || EVALUATOR.kkp.is_null() } {
+        return Err("Error: KPP and KKP aren't loaded.".to_string());
+    }
     if let Err(err) = unsafe { EVALUATOR.write_kpp(kpp_file_name) } {
         return Err(format!("{}\nFile name: {}", err, kpp_file_name));
     }
@@ -653,13 +737,111 @@ pub fn write_evaluate_files() -> Result<(), String> {
 }
 
 pub fn evaluate(pos: &mut Position, stack: &mut [Stack], ehash: *mut EvalHash) -> Value {
-    unsafe { EVALUATOR.evaluate_difference_calc(pos, stack, ehash) }
+    #[cfg(feature = "nnue")]
+    {
+        if let Some(value) = crate::nnue::evaluate(pos, stack) {
+            #[cfg(test)]
+            check_evaluate(pos, value);
+            return value;
+        }
+    }
+    let value = unsafe { EVALUATOR.evaluate_difference_calc(pos, stack, ehash) };
+    #[cfg(test)]
+    check_evaluate(pos, value);
+    value
+}
+
+// The number of the calls of evaluate(). Every 64th one is compared with the full evaluation.
+#[cfg(test)]
+pub static EVALUATE_CALLS: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+// The incremental evaluation of the search must agree with the full evaluation.
+// Only a sampling of the nodes is checked, because the full evaluation is slow.
+#[cfg(test)]
+fn check_evaluate(pos: &Position, value: Value) {
+    if EVALUATE_CALLS.fetch_add(1, std::sync::atomic::Ordering::Relaxed) % 64 == 0 {
+        assert_eq!(value, evaluate_static(pos));
+    }
 }
 
 pub fn evaluate_at_root(pos: &Position, stack: &mut [Stack]) -> Value {
+    #[cfg(feature = "nnue")]
+    {
+        if let Some(value) = crate::nnue::evaluate_at_root(pos, stack) {
+            return value;
+        }
+    }
     unsafe { EVALUATOR.evaluate_at_root(pos, stack) }
 }
 
+// Static evaluation from side to move's perspective without search stack and EvalHash.
+pub fn evaluate_static(pos: &Position) -> Value {
+    evaluate_static_with_sum(pos).0
+}
+
+pub fn evaluate_static_with_sum(pos: &Position) -> (Value, EvalSum) {
+    let mut stack = [Stack::new(); CURRENT_STACK_INDEX + 1];
+    let value = evaluate_at_root(pos, &mut stack);
+    (value, get_stack(&stack, 0).static_eval_raw)
+}
+
+// The terms of evaluate_static() for debugging. The board terms are from black's perspective.
+pub fn evaluate_static_breakdown(pos: &Position) -> String {
+    let (value, sum) = evaluate_static_with_sum(pos);
+    let material = pos.material().0;
+    let mut s = format!("material: {}\n", material);
+    s += &format!(
+        "kpp black king: board {} turn {}\n",
+        sum.val[0][0] / FV_SCALE,
+        sum.val[0][1] / FV_SCALE
+    );
+    s += &format!(
+        "kpp white king: board {} turn {}\n",
+        -sum.val[1][0] / FV_SCALE,
+        sum.val[1][1] / FV_SCALE
+    );
+    s += &format!(
+        "kkp: board {} turn {}\n",
+        sum.val[2][0] / FV_SCALE - material,
+        sum.val[2][1] / FV_SCALE
+    );
+    s += &format!("total (side to move): {}", value.0);
+    s
+}
+
+// Must be called just after pos.do_move(last_move, ..). last_move must not be Move::NULL.
+// This takes the search stack instead of the previous Value, because a Value alone can't carry
+// the KPP and KKP partial sums which are updated by the changed eval indices.
+// get_stack(stack, -1) has the sums of the position before last_move, and they are set by
+// evaluate_at_root() or the previous call. The sums of "pos" are stored to get_stack(stack, 0),
+// so the next move is evaluated with &mut stack[1..] as the search does, and nothing is allocated.
+#[allow(dead_code)]
+pub fn evaluate_incremental(
+    pos: &mut Position,
+    last_move: Move,
+    stack: &mut [Stack],
+    ehash: &mut EvalHash,
+) -> Value {
+    get_stack_mut(stack, -1).current_move = Some(last_move);
+    get_stack_mut(stack, 0).static_eval_raw.set_not_evaluated();
+    evaluate(pos, stack, ehash)
+}
+
+// Evaluation is from side to move's perspective, so the color flipped position has the same value.
+#[allow(dead_code)]
+pub fn eval_is_symmetric(pos: &Position) -> bool {
+    evaluate_static(pos) == evaluate_static(&pos.flip())
+}
+
+#[allow(dead_code)]
+pub fn evaluate_material(pos: &Position) -> Value {
+    if pos.side_to_move() == Color::BLACK {
+        pos.material()
+    } else {
+        -pos.material()
+    }
+}
+
 #[repr(align(256))]
 #[derive(Clone, Copy)]
 pub struct EvalSum {
@@ -975,3 +1157,148 @@ fn test_eval_index_inverse() {
         EvalIndex(EvalIndex::F_DRAGON.0 + Square::SQ98.0 as usize)
     );
 }
+
+#[test]
+fn test_evaluate_static() {
+    let usi_options = crate::usioption::UsiOptions::new();
+    load_evaluate_files(&usi_options.get_string("Eval_Dir")).unwrap();
+
+    let pos = Position::new();
+    assert_eq!(evaluate_material(&pos), Value(0));
+    assert!(evaluate_static(&pos).0.abs() < 200);
+
+    // Black has an extra rook.
+    let sfen = "lnsgkgsnl/7b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b R 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    assert!(evaluate_material(&pos) > Value(500));
+    assert!(evaluate_static(&pos) > Value(500));
+
+    let sfen = "lnsgkgsnl/7b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w R 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    assert!(evaluate_material(&pos) < Value(-500));
+    assert!(evaluate_static(&pos) < Value(-500));
+}
+
+#[test]
+fn test_evaluate_incremental() {
+    let usi_options = crate::usioption::UsiOptions::new();
+    load_evaluate_files(&usi_options.get_string("Eval_Dir")).unwrap();
+    let mut thread_pool = ThreadPool::new();
+    let mut ehash = EvalHash::new();
+    ehash.resize(1, &mut thread_pool);
+
+    let mut pos = Position::new();
+    let mut stack = [Stack::new(); CURRENT_STACK_INDEX + 9];
+    evaluate_at_root(&pos, &mut stack);
+    // includes a promotion, captures, a king move and a drop.
+    for (i, usi_str) in [
+        "7g7f", "3c3d", "8h2b+", "3a2b", "5i6h", "B*4e", "6h7h", "4e2g+",
+    ]
+    .iter()
+    .enumerate()
+    {
+        let m = Move::new_from_usi_str(usi_str, &pos).unwrap();
+        let gives_check = pos.gives_check(m);
+        pos.do_move(m, gives_check);
+        let value = evaluate_incremental(&mut pos, m, &mut stack[i + 1..], &mut ehash);
+        assert_eq!(value, evaluate_static(&pos));
+    }
+}
+
+#[test]
+fn test_evaluate_with_stack() {
+    use rand::{Rng, SeedableRng};
+    let usi_options = crate::usioption::UsiOptions::new();
+    load_evaluate_files(&usi_options.get_string("Eval_Dir")).unwrap();
+    let mut thread_pool = ThreadPool::new();
+    let mut ehash = EvalHash::new();
+    ehash.resize(1, &mut thread_pool);
+
+    // Play random moves and null moves, and evaluate each position with the previous EvalSum
+    // in the same way as the search does. The nodes of a real search are checked by
+    // thread::test_evaluate_during_search.
+    const MAX_PLY: usize = 200;
+    let mut rng: rand::rngs::StdRng = SeedableRng::seed_from_u64(20190224);
+    let mut stack = vec![Stack::new(); CURRENT_STACK_INDEX + MAX_PLY + 1];
+    let mut pos = Position::new();
+    assert_eq!(evaluate_at_root(&pos, &mut stack), evaluate_static(&pos));
+    for ply in 0..MAX_PLY {
+        if pos.game_result().is_some() {
+            break;
+        }
+        let ss = &mut stack[ply..];
+        if !pos.in_check()
+            && get_stack(ss, -1).current_move != Some(Move::NULL)
+            && rng.gen_range(0, 8) == 0
+        {
+            get_stack_mut(ss, 0).current_move = Some(Move::NULL);
+            pos.do_null_move();
+            get_stack_mut(ss, 1).static_eval_raw = get_stack(ss, 0).static_eval_raw;
+        } else {
+            let mut mlist = MoveList::new();
+            mlist.generate::<LegalType>(&pos, 0);
+            let m = mlist.slice(0)[rng.gen_range(0, mlist.size)].mv;
+            get_stack_mut(ss, 0).current_move = Some(m);
+            let gives_check = pos.gives_check(m);
+            pos.do_move(m, gives_check);
+            get_stack_mut(ss, 1).static_eval_raw.set_not_evaluated();
+        }
+        let value = evaluate(&mut pos, &mut stack[ply + 1..], &mut ehash);
+        assert_eq!(value, evaluate_static(&pos));
+    }
+}
+
+#[test]
+fn test_load_eval_from_bytes() {
+    assert_eq!(Evaluator::check_size("KPP", 4, 4), Ok(()));
+    assert_eq!(
+        Evaluator::check_size("KPP", 4, 3),
+        Err(EvalLoadError::SizeMismatch {
+            name: "KPP".to_string(),
+            expected: 4,
+            actual: 3,
+        })
+    );
+    // The size is checked before the buffers are written.
+    let size_error = |actual| {
+        Err(EvalLoadError::SizeMismatch {
+            name: "KPP and KKP".to_string(),
+            expected: Evaluator::KPP_BYTES + Evaluator::KKP_BYTES,
+            actual,
+        })
+    };
+    assert_eq!(load_eval_from_bytes(&[]), size_error(0));
+    assert_eq!(load_eval_from_bytes(&[0; 4]), size_error(4));
+    let usi_options = crate::usioption::UsiOptions::new();
+    let eval_dir = std::path::PathBuf::from(usi_options.get_string("Eval_Dir"));
+    assert!(load_eval_from_path(&eval_dir.join("no_such_dir")).is_err());
+}
+
+// This reads the whole KPP.bin and KKP.bin, and overwrites EVALUATOR while the other tests run.
+// cargo test -- --ignored test_load_eval_from_bytes_full --test-threads=1
+#[test]
+#[ignore]
+fn test_load_eval_from_bytes_full() {
+    let usi_options = crate::usioption::UsiOptions::new();
+    let eval_dir = std::path::PathBuf::from(usi_options.get_string("Eval_Dir"));
+    assert!(load_eval_from_path(&eval_dir).is_ok());
+    let mut bytes = std::fs::read(eval_dir.join("KPP.bin")).unwrap();
+    bytes.extend(std::fs::read(eval_dir.join("KKP.bin")).unwrap());
+    assert_eq!(load_eval_from_bytes(&bytes), Ok(()));
+}
+
+#[test]
+fn test_eval_is_symmetric() {
+    let usi_options = crate::usioption::UsiOptions::new();
+    load_evaluate_files(&usi_options.get_string("Eval_Dir")).unwrap();
+    let sfens = [
+        "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1",
+        "l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w RGgsn5p 1",
+        "l4S2l/4g1gs1/5p1p1/pr2N1pkp/4Gn3/PP3PPPP/2GPP4/1K7/L3r+s2L w BS2N5Pb 20",
+        "6n1l/2+S1k4/2lp4p/1np1B2b1/3PP4/1N1S3rP/1P2+pPP+p1/1p1G5/3KG2r1 b GSN2L4Pgs2p 399",
+    ];
+    for sfen in sfens.iter() {
+        let pos = Position::new_from_sfen(sfen).unwrap();
+        assert!(eval_is_symmetric(&pos));
+    }
+}
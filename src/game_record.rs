@@ -0,0 +1,184 @@
+// Game record with the evaluation and the PV of each move, for annotated CSA and KIF files.
+use crate::kif::*;
+use crate::movegen::*;
+use crate::position::*;
+use crate::sfen::*;
+use crate::types::*;
+use std::sync::atomic::AtomicI64;
+use std::sync::Arc;
+
+pub struct GameRecordEntry {
+    pub mv: Move,
+    // from the point of view of the side which played mv.
+    pub value: Value,
+    // the expected continuation after mv. (mv itself isn't included.)
+    pub pv: Vec<Move>,
+}
+
+pub struct GameRecord {
+    start_sfen: String,
+    entries: Vec<GameRecordEntry>,
+}
+
+impl GameRecord {
+    #[allow(dead_code)]
+    pub fn new(start: &Position) -> GameRecord {
+        GameRecord {
+            start_sfen: start.to_sfen(),
+            entries: vec![],
+        }
+    }
+    #[allow(dead_code)]
+    pub fn push(&mut self, mv: Move, value: Value, pv: Vec<Move>) {
+        self.entries.push(GameRecordEntry { mv, value, pv });
+    }
+    #[allow(dead_code)]
+    pub fn entries(&self) -> &[GameRecordEntry] {
+        &self.entries
+    }
+    fn start_position(&self) -> Position {
+        Position::new_from_sfen(&self.start_sfen).unwrap()
+    }
+    // The evaluation is written as a Floodgate style comment "'** <value> <pv>".
+    // The value is from black's point of view there.
+    #[allow(dead_code)]
+    pub fn to_csa(&self) -> String {
+        fn csa_move(m: Move, pos: &Position) -> String {
+            let sign = if pos.side_to_move() == Color::BLACK {
+                "+"
+            } else {
+                "-"
+            };
+            sign.to_string() + &m.to_csa_string(pos)
+        }
+        let mut pos = self.start_position();
+        let mut s = "V2.2\n".to_string();
+        s += &pos.to_csa_string();
+        for entry in self.entries.iter() {
+            let value = if pos.side_to_move() == Color::BLACK {
+                entry.value
+            } else {
+                -entry.value
+            };
+            s += &csa_move(entry.mv, &pos);
+            s += "\n";
+            let gives_check = pos.gives_check(entry.mv);
+            pos.do_move(entry.mv, gives_check);
+
+            s += &format!("'** {}", value);
+            let mut pv_pos = Position::new_from_position(&pos, Arc::new(AtomicI64::new(0)));
+            for &m in entry.pv.iter() {
+                s += " ";
+                s += &csa_move(m, &pv_pos);
+                let gives_check = pv_pos.gives_check(m);
+                pv_pos.do_move(m, gives_check);
+            }
+            s += "\n";
+        }
+        s
+    }
+    // Only the start positions which have a "手合割" name are supported.
+    #[allow(dead_code)]
+    pub fn to_kif(&self) -> Result<String, String> {
+        let kind = if self.start_sfen == START_SFEN {
+            None
+        } else {
+            Some(
+                *Handicap::ALL
+                    .iter()
+                    .find(|kind| kind.to_sfen() == self.start_sfen)
+                    .ok_or_else(|| {
+                        format!("KIF can't express the start position: {}", self.start_sfen)
+                    })?,
+            )
+        };
+        let mut pos = self.start_position();
+        let mut s = format!("手合割：{}\n", handicap_to_kif_str(kind));
+        s += "手数----指手---------消費時間--\n";
+        let mut prev_to = None;
+        for (i, entry) in self.entries.iter().enumerate() {
+            s += &format!(
+                "{:>4} {}\n",
+                i + 1,
+                move_to_kif_str(entry.mv, &pos, prev_to)
+            );
+            let gives_check = pos.gives_check(entry.mv);
+            pos.do_move(entry.mv, gives_check);
+            prev_to = Some(entry.mv.to());
+
+            s += &format!("*評価値 {} 読み筋 ", entry.value);
+            let mut pv_pos = Position::new_from_position(&pos, Arc::new(AtomicI64::new(0)));
+            let mut pv_prev_to = prev_to;
+            for &m in entry.pv.iter() {
+                s += if pv_pos.side_to_move() == Color::BLACK {
+                    "▲"
+                } else {
+                    "△"
+                };
+                s += &move_to_kif_str(m, &pv_pos, pv_prev_to);
+                let gives_check = pv_pos.gives_check(m);
+                pv_pos.do_move(m, gives_check);
+                pv_prev_to = Some(m.to());
+            }
+            s += "\n";
+        }
+        Ok(s)
+    }
+}
+
+#[test]
+fn test_game_record() {
+    let mut pos = Position::new();
+    let mut record = GameRecord::new(&pos);
+    let mut play = |pos: &mut Position, usi_str: &str, value: Value, pv_strs: &[&str]| {
+        let m = Move::new_from_usi_str(usi_str, pos).unwrap();
+        let gives_check = pos.gives_check(m);
+        pos.do_move(m, gives_check);
+        let mut pv_pos = Position::new_from_position(pos, Arc::new(AtomicI64::new(0)));
+        let pv = pv_strs
+            .iter()
+            .map(|x| {
+                let m = Move::new_from_usi_str(x, &pv_pos).unwrap();
+                let gives_check = pv_pos.gives_check(m);
+                pv_pos.do_move(m, gives_check);
+                m
+            })
+            .collect();
+        record.push(m, value, pv);
+    };
+    // +50 and -40 centipawns.
+    play(&mut pos, "7g7f", Value(45), &["3c3d", "8h2b+"]);
+    play(&mut pos, "3c3d", Value(-36), &["8h2b+", "3a2b"]);
+    assert_eq!(record.entries().len(), 2);
+
+    let csa = record.to_csa();
+    assert!(csa.starts_with("V2.2\n"));
+    assert!(csa.contains("+7776FU\n'** +50 -3334FU +8822UM\n"));
+    assert!(csa.contains("-3334FU\n'** +40 +8822UM -3122GI\n"));
+    let moves: Vec<&str> = csa
+        .lines()
+        .filter(|x| x.starts_with('+') || x.starts_with('-'))
+        .filter(|x| x.len() == 7)
+        .collect();
+    assert_eq!(
+        parse_csa_moves(&Position::new(), &moves)
+            .unwrap()
+            .iter()
+            .map(|m| m.to_usi_string())
+            .collect::<Vec<_>>(),
+        ["7g7f", "3c3d"]
+    );
+
+    let kif = record.to_kif().unwrap();
+    assert!(kif.contains("*評価値 +50 読み筋 △３四歩(33)▲２二角成(88)\n"));
+    assert!(kif.contains("*評価値 -40 読み筋 ▲２二角成(88)△同　銀(31)\n"));
+    let (start, moves) = parse_kif(&kif).unwrap();
+    assert!(start == Position::new());
+    assert_eq!(
+        moves,
+        record.entries().iter().map(|x| x.mv).collect::<Vec<_>>()
+    );
+
+    let pos = Position::new_from_sfen("4k4/9/9/9/9/9/9/9/4K4 b R 1").unwrap();
+    assert!(GameRecord::new(&pos).to_kif().is_err());
+}
@@ -140,6 +140,22 @@ impl Hand {
     pub fn is_equal_or_superior(self, other: Hand) -> bool {
         (self.0.wrapping_sub(other.0) & Hand::BORROW_MASK) == 0
     }
+
+    // Same as is_equal_or_superior(). self has at least as many pieces as other for every type.
+    #[allow(dead_code)]
+    pub fn dominates(self, other: Hand) -> bool {
+        self.is_equal_or_superior(other)
+    }
+
+    // self.num(pt) - other.num(pt) in the order of PieceType::ALL_HAND.
+    #[allow(dead_code)]
+    pub fn diff(self, other: Hand) -> [i32; PieceType::ALL_HAND.len()] {
+        let mut diff = [0; PieceType::ALL_HAND.len()];
+        for (d, &pt) in diff.iter_mut().zip(PieceType::ALL_HAND.iter()) {
+            *d = self.num(pt) as i32 - other.num(pt) as i32;
+        }
+        diff
+    }
 }
 
 #[test]
@@ -194,3 +210,25 @@ fn test_hand_is_equal_or_superior() {
     assert_eq!(hand.is_equal_or_superior(hand2), false);
     assert_eq!(hand2.is_equal_or_superior(hand), false);
 }
+
+#[test]
+fn test_hand_dominates_and_diff() {
+    let mut hand = Hand(0);
+    hand.set(PieceType::PAWN, 3);
+    hand.set(PieceType::GOLD, 1);
+    hand.set(PieceType::ROOK, 1);
+    let mut hand2 = Hand(0);
+    hand2.set(PieceType::PAWN, 1);
+    hand2.set(PieceType::GOLD, 1);
+    assert!(hand.dominates(hand2));
+    assert!(!hand2.dominates(hand));
+    assert_eq!(hand.diff(hand2), [2, 0, 0, 0, 0, 1, 0]);
+    assert_eq!(hand2.diff(hand), [-2, 0, 0, 0, 0, -1, 0]);
+    assert_eq!(hand.diff(hand), [0; 7]);
+
+    // neither dominates.
+    hand2.set(PieceType::KNIGHT, 1);
+    assert!(!hand.dominates(hand2));
+    assert!(!hand2.dominates(hand));
+    assert_eq!(hand.diff(hand2), [2, 0, -1, 0, 0, 1, 0]);
+}
@@ -0,0 +1,351 @@
+// KIF game record parser.
+// Only the start positions of "手合割" are supported. (Board diagrams (BOD) are not supported.)
+use crate::movegen::*;
+use crate::position::*;
+use crate::sfen::*;
+use crate::types::*;
+use std::sync::atomic::AtomicI64;
+use std::sync::Arc;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum KifParseError {
+    UnknownHandicap { name: String },
+    InvalidMove { line_number: usize, line: String },
+    IllegalMove { line_number: usize, line: String },
+}
+
+fn handicap_from_kif_str(s: &str) -> Result<Option<Handicap>, KifParseError> {
+    match s {
+        "平手" => Ok(None),
+        "香落ち" => Ok(Some(Handicap::Lance)),
+        "右香落ち" => Ok(Some(Handicap::RightLance)),
+        "角落ち" => Ok(Some(Handicap::Bishop)),
+        "飛車落ち" => Ok(Some(Handicap::Rook)),
+        "飛香落ち" => Ok(Some(Handicap::RookLance)),
+        "二枚落ち" => Ok(Some(Handicap::TwoPieces)),
+        "四枚落ち" => Ok(Some(Handicap::FourPieces)),
+        "六枚落ち" => Ok(Some(Handicap::SixPieces)),
+        "八枚落ち" => Ok(Some(Handicap::EightPieces)),
+        "十枚落ち" => Ok(Some(Handicap::TenPieces)),
+        _ => Err(KifParseError::UnknownHandicap {
+            name: s.to_string(),
+        }),
+    }
+}
+
+#[allow(dead_code)]
+pub fn handicap_to_kif_str(kind: Option<Handicap>) -> &'static str {
+    match kind {
+        None => "平手",
+        Some(Handicap::Lance) => "香落ち",
+        Some(Handicap::RightLance) => "右香落ち",
+        Some(Handicap::Bishop) => "角落ち",
+        Some(Handicap::Rook) => "飛車落ち",
+        Some(Handicap::RookLance) => "飛香落ち",
+        Some(Handicap::TwoPieces) => "二枚落ち",
+        Some(Handicap::FourPieces) => "四枚落ち",
+        Some(Handicap::SixPieces) => "六枚落ち",
+        Some(Handicap::EightPieces) => "八枚落ち",
+        Some(Handicap::TenPieces) => "十枚落ち",
+    }
+}
+
+fn file_from_kif_char(c: char) -> Option<File> {
+    match c {
+        '１'..='９' => Some(File(c as i32 - '１' as i32)),
+        '1'..='9' => Some(File(c as i32 - '1' as i32)),
+        _ => None,
+    }
+}
+
+fn rank_from_kif_char(c: char) -> Option<Rank> {
+    "一二三四五六七八九"
+        .chars()
+        .position(|x| x == c)
+        .map(|i| Rank(i as i32))
+}
+
+// Returns the piece type and the rest of the string.
+fn piece_type_from_kif_str(s: &str) -> Option<(PieceType, &str)> {
+    const PIECE_TYPES: [(&str, PieceType); 19] = [
+        ("成香", PieceType::PRO_LANCE),
+        ("成桂", PieceType::PRO_KNIGHT),
+        ("成銀", PieceType::PRO_SILVER),
+        ("歩", PieceType::PAWN),
+        ("香", PieceType::LANCE),
+        ("桂", PieceType::KNIGHT),
+        ("銀", PieceType::SILVER),
+        ("金", PieceType::GOLD),
+        ("角", PieceType::BISHOP),
+        ("飛", PieceType::ROOK),
+        ("玉", PieceType::KING),
+        ("王", PieceType::KING),
+        ("と", PieceType::PRO_PAWN),
+        ("杏", PieceType::PRO_LANCE),
+        ("圭", PieceType::PRO_KNIGHT),
+        ("全", PieceType::PRO_SILVER),
+        ("馬", PieceType::HORSE),
+        ("龍", PieceType::DRAGON),
+        ("竜", PieceType::DRAGON),
+    ];
+    PIECE_TYPES
+        .iter()
+        .find(|(name, _)| s.starts_with(name))
+        .map(|&(name, pt)| (pt, &s[name.len()..]))
+}
+
+fn piece_type_to_kif_str(pt: PieceType) -> &'static str {
+    match pt {
+        PieceType::PAWN => "歩",
+        PieceType::LANCE => "香",
+        PieceType::KNIGHT => "桂",
+        PieceType::SILVER => "銀",
+        PieceType::GOLD => "金",
+        PieceType::BISHOP => "角",
+        PieceType::ROOK => "飛",
+        PieceType::KING => "玉",
+        PieceType::PRO_PAWN => "と",
+        PieceType::PRO_LANCE => "成香",
+        PieceType::PRO_KNIGHT => "成桂",
+        PieceType::PRO_SILVER => "成銀",
+        PieceType::HORSE => "馬",
+        PieceType::DRAGON => "龍",
+        _ => unreachable!(),
+    }
+}
+
+// The inverse of move_from_kif_str(). m must be a move of pos.
+#[allow(dead_code)]
+pub fn move_to_kif_str(m: Move, pos: &Position, prev_to: Option<Square>) -> String {
+    let to = m.to();
+    let mut s = if prev_to == Some(to) {
+        "同　".to_string()
+    } else {
+        let file = std::char::from_u32('１' as u32 + File::new(to).0 as u32).unwrap();
+        let rank = "一二三四五六七八九"
+            .chars()
+            .nth(Rank::new(to).0 as usize)
+            .unwrap();
+        format!("{}{}", file, rank)
+    };
+    if m.is_drop() {
+        s += piece_type_to_kif_str(m.piece_type_dropped());
+        s += "打";
+        return s;
+    }
+    let from = m.from();
+    let pt = PieceType::new(pos.piece_on(from));
+    s += piece_type_to_kif_str(pt);
+    let us = pos.side_to_move();
+    if m.is_promotion() {
+        s += "成";
+    } else if pt.is_promotable()
+        && (Rank::new(from).is_opponent_field(us) || Rank::new(to).is_opponent_field(us))
+    {
+        s += "不成";
+    }
+    s += &format!("({}{})", File::new(from).0 + 1, Rank::new(from).0 + 1);
+    s
+}
+
+// Parses a move such as "７六歩(77)", "同　銀(31)", "２二角成(88)" or "４五角打".
+// Returns None if the string isn't a move.
+fn move_from_kif_str(s: &str, pos: &Position, prev_to: Option<Square>) -> Option<Move> {
+    let (to, rest) = if s.starts_with('同') {
+        (
+            prev_to?,
+            s['同'.len_utf8()..].trim_start_matches(&['　', ' '][..]),
+        )
+    } else {
+        let mut chars = s.chars();
+        let file = file_from_kif_char(chars.next()?)?;
+        let rank = rank_from_kif_char(chars.next()?)?;
+        (Square::new(file, rank), chars.as_str())
+    };
+    let (pt, rest) = piece_type_from_kif_str(rest)?;
+    let us = pos.side_to_move();
+    if rest.starts_with('打') {
+        return Some(Move::new_drop(Piece::new(us, pt), to));
+    }
+    let (is_promote, rest) = if let Some(rest) = rest.strip_prefix("不成") {
+        (false, rest)
+    } else if let Some(rest) = rest.strip_prefix('成') {
+        (true, rest)
+    } else {
+        (false, rest)
+    };
+    let v: Vec<char> = rest.chars().collect();
+    if v.len() < 4 || v[0] != '(' || v[3] != ')' {
+        return None;
+    }
+    let rank_from = match v[2].to_digit(10)? {
+        0 => return None,
+        r => Rank(r as i32 - 1),
+    };
+    let from = Square::new(file_from_kif_char(v[1])?, rank_from);
+    let pc_from = pos.piece_on(from);
+    if PieceType::new(pc_from) != pt || Color::new(pc_from) != us {
+        return None;
+    }
+    Some(if is_promote {
+        Move::new_promote(from, to, pc_from)
+    } else {
+        Move::new_unpromote(from, to, pc_from)
+    })
+}
+
+// Returns the start position and the moves of the main line.
+#[allow(dead_code)]
+pub fn parse_kif(text: &str) -> Result<(Position, Vec<Move>), KifParseError> {
+    let mut start = Position::new();
+    let mut pos: Option<Position> = None;
+    let mut moves = vec![];
+    let mut prev_to = None;
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.trim();
+        if line.starts_with('#') || line.starts_with('*') || line.starts_with('&') {
+            // comment
+            continue;
+        }
+        if line.starts_with("変化：") {
+            // variations aren't read.
+            break;
+        }
+        if pos.is_none() {
+            if let Some(name) = line.strip_prefix("手合割：") {
+                let name = name.trim();
+                start = match handicap_from_kif_str(name)? {
+                    Some(kind) => Position::handicap(kind),
+                    None => Position::new(),
+                };
+                continue;
+            }
+            if !line.starts_with(|c: char| c.is_ascii_digit()) {
+                // other header lines.
+                continue;
+            }
+            pos = Some(Position::new_from_position(
+                &start,
+                Arc::new(AtomicI64::new(0)),
+            ));
+        }
+        let pos = pos.as_mut().unwrap();
+        let move_str = line
+            .trim_start_matches(|c: char| c.is_ascii_digit())
+            .trim_start()
+            .split(|c: char| c.is_ascii_whitespace()) // "同　銀" has a full-width space.
+            .next()
+            .unwrap_or("");
+        if move_str.is_empty() {
+            continue;
+        }
+        let is_move = move_str.starts_with('同')
+            || move_str
+                .chars()
+                .next()
+                .and_then(file_from_kif_char)
+                .is_some();
+        if !is_move {
+            // "投了", "中断", "詰み" and so on.
+            break;
+        }
+        let m = match move_from_kif_str(move_str, pos, prev_to) {
+            Some(m) => m,
+            None => {
+                return Err(KifParseError::InvalidMove {
+                    line_number,
+                    line: line.to_string(),
+                })
+            }
+        };
+        if !pos.pseudo_legal::<NotSearchingType>(m) || !pos.legal(m) {
+            return Err(KifParseError::IllegalMove {
+                line_number,
+                line: line.to_string(),
+            });
+        }
+        let gives_check = pos.gives_check(m);
+        pos.do_move(m, gives_check);
+        moves.push(m);
+        prev_to = Some(m.to());
+    }
+    Ok((start, moves))
+}
+
+#[test]
+fn test_parse_kif() {
+    let text = "# ---- Kifu for Windows ----
+開始日時：2019/01/01
+手合割：平手
+先手：black
+後手：white
+手数----指手---------消費時間--
+   1 ７六歩(77)   ( 0:01/00:00:01)
+   2 ３四歩(33)   ( 0:01/00:00:01)
+*comment
+   3 ２二角成(88)   ( 0:01/00:00:02)
+   4 同　銀(31)   ( 0:01/00:00:02)
+   5 ４五角打   ( 0:01/00:00:03)
+   6 投了
+";
+    let (pos, moves) = parse_kif(text).unwrap();
+    assert!(pos == Position::new());
+    let usi_strs: Vec<String> = moves.iter().map(|m| m.to_usi_string()).collect();
+    assert_eq!(usi_strs, ["7g7f", "3c3d", "8h2b+", "3a2b", "B*4e"]);
+
+    let mut pos = Position::new();
+    let mut prev_to = None;
+    let mut kif_strs = vec![];
+    for &m in moves.iter() {
+        kif_strs.push(move_to_kif_str(m, &pos, prev_to));
+        let gives_check = pos.gives_check(m);
+        pos.do_move(m, gives_check);
+        prev_to = Some(m.to());
+    }
+    assert_eq!(
+        kif_strs,
+        [
+            "７六歩(77)",
+            "３四歩(33)",
+            "２二角成(88)",
+            "同　銀(31)",
+            "４五角打"
+        ]
+    );
+
+    let text = "手合割：角落ち
+手数----指手---------消費時間--
+   1 ３四歩(33)
+   2 ７六歩(77)
+";
+    let (pos, moves) = parse_kif(text).unwrap();
+    assert!(pos == Position::handicap(Handicap::Bishop));
+    assert_eq!(moves.len(), 2);
+
+    let text = "手合割：平手
+   1 ７六歩(77)
+   2 ３四歩(28)
+";
+    assert_eq!(
+        parse_kif(text).err(),
+        Some(KifParseError::InvalidMove {
+            line_number: 3,
+            line: "2 ３四歩(28)".to_string()
+        })
+    );
+    let text = "   1 ７五歩(77)\n";
+    assert_eq!(
+        parse_kif(text).err(),
+        Some(KifParseError::IllegalMove {
+            line_number: 1,
+            line: "1 ７五歩(77)".to_string()
+        })
+    );
+    assert_eq!(
+        parse_kif("手合割：その他\n").err(),
+        Some(KifParseError::UnknownHandicap {
+            name: "その他".to_string()
+        })
+    );
+}
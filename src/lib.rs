@@ -11,13 +11,18 @@ extern crate enum_derive;
 #[macro_use]
 extern crate lazy_static;
 mod authors;
+mod bench;
 mod bitboard;
 mod engine_name;
 mod evaluate;
 mod file_to_vec;
+mod game_record;
 mod hand;
+mod kif;
 mod movegen;
 mod movepick;
+#[cfg(feature = "nnue")]
+mod nnue;
 mod piecevalue;
 mod position;
 mod search;
@@ -26,5 +31,6 @@ mod thread;
 mod timeman;
 mod tt;
 mod types;
+mod usen;
 pub mod usi;
 mod usioption;
@@ -95,10 +95,7 @@ impl Move {
                 return None;
             }
         }
-        if !pos.pseudo_legal::<NotSearchingType>(m) || !pos.legal(m) {
-            return None;
-        }
-        Some(m)
+        m.if_legal(pos)
     }
     pub fn new_from_csa_str(s: &str, pos: &Position) -> Option<Move> {
         let m;
@@ -142,17 +139,75 @@ impl Move {
                 }
             };
             if is_promote {
-                m = Move::new_promote(from, to, pc);
+                m = Move::new_promote(from, to, pos.piece_on(from));
             } else {
                 m = Move::new_unpromote(from, to, pc);
             }
         }
 
-        if !pos.pseudo_legal::<NotSearchingType>(m) || !pos.legal(m) {
+        m.if_legal(pos)
+    }
+    // Returns Some only if the move is legal in pos.
+    #[allow(dead_code)]
+    pub fn try_new_unpromote(from: Square, to: Square, pos: &Position) -> Option<Move> {
+        let pc = pos.piece_on(from);
+        if pc == Piece::EMPTY {
             return None;
         }
-
-        Some(m)
+        Move::new_unpromote(from, to, pc).if_legal(pos)
+    }
+    #[allow(dead_code)]
+    pub fn try_new_promote(from: Square, to: Square, pos: &Position) -> Option<Move> {
+        let pc = pos.piece_on(from);
+        if pc == Piece::EMPTY {
+            return None;
+        }
+        Move::new_promote(from, to, pc).if_legal(pos)
+    }
+    #[allow(dead_code)]
+    pub fn try_new_drop(pc: Piece, to: Square, pos: &Position) -> Option<Move> {
+        Move::new_drop(pc, to).if_legal(pos)
+    }
+    // The moved piece isn't included. (The same as the move of TTEntry.)
+    #[allow(dead_code)]
+    pub fn to_u16(self) -> u16 {
+        self.0.get() as u16
+    }
+    // Returns Some only if the move is legal in pos.
+    #[allow(dead_code)]
+    pub fn new_from_u16(v: u16, pos: &Position) -> Option<Move> {
+        let m = Move(std::num::NonZeroU32::new(u32::from(v))?);
+        if Square::NUM <= m.to().0 as usize {
+            return None;
+        }
+        if m.is_drop() {
+            if u32::from(v) & !(Move::DROP_FLAG | Move::PIECE_DROPPED_MASK | Move::TO_MASK) != 0
+                || !PieceType::ALL_HAND.contains(&m.piece_type_dropped())
+            {
+                return None;
+            }
+            return m.if_legal(pos);
+        }
+        let from = m.from();
+        if Square::NUM <= from.0 as usize {
+            return None;
+        }
+        let pc = pos.piece_on(from);
+        if pc == Piece::EMPTY {
+            return None;
+        }
+        Move(unsafe {
+            std::num::NonZeroU32::new_unchecked(
+                m.0.get() | ((pc.0 as u32) << Move::MOVED_PIECE_SHIFT),
+            )
+        })
+        .if_legal(pos)
+    }
+    fn if_legal(self, pos: &Position) -> Option<Move> {
+        if !pos.pseudo_legal::<NotSearchingType>(self) || !pos.legal(self) {
+            return None;
+        }
+        Some(self)
     }
     #[inline]
     pub fn to(self) -> Square {
@@ -161,6 +216,24 @@ impl Move {
     pub fn from(self) -> Square {
         Square(((self.0.get() & Move::FROM_MASK) >> Move::FROM_SHIFT) as i32)
     }
+    // None for drops.
+    #[allow(dead_code, clippy::wrong_self_convention)]
+    pub fn from_opt(self) -> Option<Square> {
+        if self.is_drop() {
+            None
+        } else {
+            Some(self.from())
+        }
+    }
+    // Some only for drops.
+    #[allow(dead_code)]
+    pub fn dropped_piece_type(self) -> Option<PieceType> {
+        if self.is_drop() {
+            Some(self.piece_type_dropped())
+        } else {
+            None
+        }
+    }
     pub fn piece_dropped(self) -> Piece {
         Piece(((self.0.get() & Move::PIECE_DROPPED_MASK) >> Move::PIECE_DROPPED_SHIFT) as i32)
     }
@@ -197,7 +270,8 @@ impl Move {
     }
     // You can use this function only before Position::do_move() with this move.
     pub fn is_capture(self, pos: &Position) -> bool {
-        pos.piece_on(self.to()) != Piece::EMPTY
+        let pc = pos.piece_on(self.to());
+        pc != Piece::EMPTY && Color::new(pc) != pos.side_to_move()
     }
     pub fn is_pawn_promotion(self) -> bool {
         self.is_promotion() && PieceType::new(self.piece_moved_before_move()) == PieceType::PAWN
@@ -206,6 +280,16 @@ impl Move {
     pub fn is_capture_or_pawn_promotion(self, pos: &Position) -> bool {
         self.is_capture(pos) || self.is_pawn_promotion()
     }
+    // A capture or any promotion. You can use this function only before Position::do_move() with this move.
+    #[allow(dead_code)]
+    pub fn is_tactical(self, pos: &Position) -> bool {
+        self.is_capture(pos) || self.is_promotion()
+    }
+    // Neither a capture nor a promotion. You can use this function only before Position::do_move() with this move.
+    #[allow(dead_code)]
+    pub fn is_quiet(self, pos: &Position) -> bool {
+        !self.is_tactical(pos)
+    }
     pub fn to_usi_string(self) -> String {
         let mut s = "".to_string();
         if self.is_drop() {
@@ -244,6 +328,42 @@ impl Move {
     }
 }
 
+#[derive(Debug, PartialEq, Eq)]
+pub enum CsaParseError {
+    IllegalMove { index: usize, line: String },
+}
+
+// Parses CSA moves such as "+7776FU" from pos. The sign may be omitted.
+// Comment lines (') and consumption time lines (T) are skipped.
+#[allow(dead_code)]
+pub fn parse_csa_moves(pos: &Position, lines: &[&str]) -> Result<Vec<Move>, CsaParseError> {
+    let mut pos = Position::new_from_position(
+        pos,
+        std::sync::Arc::new(std::sync::atomic::AtomicI64::new(0)),
+    );
+    let mut moves = vec![];
+    for (index, line) in lines.iter().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('\'') || line.starts_with('T') {
+            continue;
+        }
+        let illegal_move = || CsaParseError::IllegalMove {
+            index,
+            line: line.to_string(),
+        };
+        let move_str = match (line.chars().next(), pos.side_to_move()) {
+            (Some('+'), Color::BLACK) | (Some('-'), Color::WHITE) => &line[1..],
+            (Some('+'), _) | (Some('-'), _) => return Err(illegal_move()),
+            _ => line,
+        };
+        let m = Move::new_from_csa_str(move_str, &pos).ok_or_else(illegal_move)?;
+        let gives_check = pos.gives_check(m);
+        pos.do_move(m, gives_check);
+        moves.push(m);
+    }
+    Ok(moves)
+}
+
 pub trait UnwrapUnchecked {
     fn unwrap_unchecked(self) -> Move;
 }
@@ -460,7 +580,7 @@ impl MoveList {
         }
 
         let target_drop = Bitboard::between_mask(checker_sq, ksq_of_evasion);
-        let target_move = target_drop | Bitboard::square_mask(checker_sq);
+        let target_move = pos.check_resolution_squares();
 
         self.generate_for_piece::<PawnType, EvasionsType>(pos, &target_move);
         self.generate_for_piece::<LanceType, EvasionsType>(pos, &target_move);
@@ -474,6 +594,24 @@ impl MoveList {
 
         self.generate_drop::<EvasionsType>(pos, &target_drop);
     }
+    // drops are never illegal because of our own king, so only the drop rules and the check
+    // matter.
+    #[allow(dead_code)]
+    pub fn generate_legal_drops(&mut self, pos: &Position, current_size: usize) {
+        self.size = current_size;
+        let checkers = pos.checkers();
+        if checkers.to_bool() {
+            if pos.is_double_check() {
+                return;
+            }
+            let ksq = pos.king_square(pos.side_to_move());
+            let target = Bitboard::between_mask(checkers.lsb_unchecked(), ksq);
+            self.generate_drop::<EvasionsType>(pos, &target);
+        } else {
+            let target = pos.empty_bb();
+            self.generate_drop::<NonEvasionsType>(pos, &target);
+        }
+    }
     fn generate_drop_for_possessions(&mut self, possessions: &[Piece], to_bb: Bitboard) {
         for to in to_bb {
             for &pc in possessions {
@@ -519,7 +657,7 @@ impl MoveList {
             let them = us.inverse();
             let ksq = pos.king_square(them);
             let drop_pawn_check_bb = ATTACK_TABLE.pawn.attack(them, ksq);
-            if (drop_pawn_check_bb & to_bb).to_bool() {
+            if !pos.allow_drop_pawn_mate() && (drop_pawn_check_bb & to_bb).to_bool() {
                 debug_assert_eq!(drop_pawn_check_bb.count_ones(), 1);
                 let to = drop_pawn_check_bb.lsb_unchecked();
                 if pos.is_drop_pawn_mate(us, to) {
@@ -1012,6 +1150,14 @@ impl MoveList {
             }
         }
     }
+    #[allow(dead_code)]
+    pub fn generate_legal_except(&mut self, pos: &Position, current_size: usize, excluded: Move) {
+        self.generate_legals(pos, current_size);
+        if let Some(i) = (current_size..self.size).find(|&i| self.ext_moves[i].mv == excluded) {
+            self.size -= 1;
+            self.ext_moves[i].mv = self.ext_moves[self.size].mv;
+        }
+    }
     pub fn generate<AMT: AllowMovesTrait>(&mut self, pos: &Position, current_size: usize) {
         if AMT::LEGALS {
             self.generate_legals(pos, current_size);
@@ -1078,6 +1224,66 @@ fn test_move_piece_moved() {
     }
 }
 
+#[test]
+fn test_move_try_new() {
+    let sfen = "lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    assert_eq!(
+        Move::try_new_unpromote(Square::SQ33, Square::SQ34, &pos),
+        Move::new_from_usi_str("3c3d", &pos)
+    );
+    assert!(Move::try_new_unpromote(Square::SQ33, Square::SQ34, &pos).is_some());
+    // not side to move.
+    assert!(Move::try_new_unpromote(Square::SQ76, Square::SQ75, &pos).is_none());
+    // no piece.
+    assert!(Move::try_new_unpromote(Square::SQ55, Square::SQ56, &pos).is_none());
+    // promotion out of the opponent field.
+    assert!(Move::try_new_promote(Square::SQ33, Square::SQ34, &pos).is_none());
+    // no piece in hand.
+    assert!(Move::try_new_drop(Piece::W_PAWN, Square::SQ55, &pos).is_none());
+
+    let sfen = "4k4/9/4P4/9/9/9/9/9/4K4 b G 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    assert!(Move::try_new_promote(Square::SQ53, Square::SQ52, &pos).is_some());
+    assert!(Move::try_new_drop(Piece::B_GOLD, Square::SQ55, &pos).is_some());
+    assert!(Move::try_new_drop(Piece::B_GOLD, Square::SQ53, &pos).is_none());
+    // the king moves into check.
+    let sfen = "4k4/9/9/9/9/9/9/3r5/4K4 b - 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    assert!(Move::try_new_unpromote(Square::SQ59, Square::SQ58, &pos).is_none());
+    assert!(Move::try_new_unpromote(Square::SQ59, Square::SQ49, &pos).is_some());
+}
+
+#[test]
+fn test_move_u16() {
+    let mut pos = Position::new();
+    for move_str in &["7g7f", "3c3d", "8h2b+", "3a2b", "B*4e"] {
+        let m = Move::new_from_usi_str(move_str, &pos).unwrap();
+        assert_eq!(Move::new_from_u16(m.to_u16(), &pos), Some(m));
+        let gives_check = pos.gives_check(m);
+        pos.do_move(m, gives_check);
+    }
+    assert!(Move::new_from_u16(0, &pos).is_none());
+    assert!(Move::new_from_u16(Move::NULL.to_u16(), &pos).is_none());
+    assert!(Move::new_from_u16(0xffff, &pos).is_none());
+    // no piece on from.
+    let m = Move::new_unpromote(Square::SQ55, Square::SQ56, Piece::W_PAWN);
+    assert!(Move::new_from_u16(m.to_u16(), &pos).is_none());
+}
+
+#[test]
+fn test_move_is_capture() {
+    let sfen = "lnsgkgsnl/1r5b1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL b - 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    // onto a friendly piece.
+    let m = Move::new_unpromote(Square::SQ88, Square::SQ77, Piece::B_BISHOP);
+    assert!(!m.is_capture(&pos));
+    let m = Move::new_from_usi_str("8h2b+", &pos).unwrap();
+    assert!(m.is_capture(&pos));
+    let m = Move::new_from_usi_str("8h7g", &pos).unwrap();
+    assert!(!m.is_capture(&pos));
+}
+
 #[test]
 fn test_generate_for_piece() {
     let sfen = "4k4/9/9/9/9/9/4l4/4bp3/4KP3 b - 1";
@@ -1985,6 +2191,40 @@ fn test_move_new_from_csa_str() {
     assert!(Move::new_from_csa_str(m_str_illegal, &pos).is_none());
 }
 
+#[test]
+fn test_parse_csa_moves() {
+    let pos = Position::new();
+    let lines = [
+        "+7776FU", "T3", "-3334FU", "'comment", "+8822UM", "-3122GI", "+0045KA",
+    ];
+    let moves = parse_csa_moves(&pos, &lines).unwrap();
+    let usi_strs: Vec<String> = moves.iter().map(|m| m.to_usi_string()).collect();
+    assert_eq!(usi_strs, ["7g7f", "3c3d", "8h2b+", "3a2b", "B*4e"]);
+    // the original position isn't changed.
+    assert!(pos == Position::new());
+
+    let lines = ["7776FU", "3334FU"];
+    assert_eq!(parse_csa_moves(&pos, &lines).unwrap().len(), 2);
+
+    let lines = ["+7776FU", "-3334FU", "+7675FU", "-8822UM"];
+    assert_eq!(
+        parse_csa_moves(&pos, &lines),
+        Err(CsaParseError::IllegalMove {
+            index: 3,
+            line: "-8822UM".to_string()
+        })
+    );
+    // wrong side to move.
+    let lines = ["+7776FU", "+2726FU"];
+    assert_eq!(
+        parse_csa_moves(&pos, &lines),
+        Err(CsaParseError::IllegalMove {
+            index: 1,
+            line: "+2726FU".to_string()
+        })
+    );
+}
+
 #[test]
 fn test_pawn_drop_mate() {
     let sfen = "kl7/1n7/K8/9/9/9/9/9/9 b P 1";
@@ -2012,3 +2252,135 @@ fn test_is_normal_move() {
     .is_normal_move());
     assert!(Some(Move::new_drop(Piece::B_PAWN, Square::SQ12)).is_normal_move());
 }
+
+#[test]
+fn test_move_is_quiet_and_is_tactical() {
+    let sfen = "4k4/9/4p4/9/9/2S6/9/4G4/4K4 b P 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    // a quiet gold move.
+    let m = Move::new_from_usi_str("5h5g", &pos).unwrap();
+    assert!(m.is_quiet(&pos));
+    assert!(!m.is_tactical(&pos));
+    // a drop.
+    let m = Move::new_from_usi_str("P*1e", &pos).unwrap();
+    assert!(m.is_quiet(&pos));
+    // a silver move without promotion.
+    let m = Move::new_from_usi_str("7f7e", &pos).unwrap();
+    assert!(m.is_quiet(&pos));
+    // a silver promotion. It isn't a pawn promotion, but it is tactical.
+    let sfen = "4k4/9/4p4/2S6/9/9/9/4G4/4K4 b - 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    let m = Move::new_from_usi_str("7d7c+", &pos).unwrap();
+    assert!(!m.is_capture_or_pawn_promotion(&pos));
+    assert!(m.is_tactical(&pos));
+    assert!(!m.is_quiet(&pos));
+    // a capture.
+    let sfen = "4k4/9/4p4/9/9/9/9/9/1K2R4 b - 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    let m = Move::new_from_usi_str("5i5c", &pos).unwrap();
+    assert!(m.is_tactical(&pos));
+    assert!(!m.is_quiet(&pos));
+}
+
+#[test]
+fn test_move_from_opt_and_dropped_piece_type() {
+    let sfen = "4k4/9/9/9/9/9/9/4G4/4K4 b S 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    let m = Move::new_from_usi_str("S*3e", &pos).unwrap();
+    assert_eq!(m.from_opt(), None);
+    assert_eq!(m.dropped_piece_type(), Some(PieceType::SILVER));
+    let m = Move::new_from_usi_str("5h4g", &pos).unwrap();
+    assert_eq!(m.from_opt(), Some(Square::SQ58));
+    assert_eq!(m.dropped_piece_type(), None);
+}
+
+#[test]
+fn test_move_list_generate_legal_drops() {
+    let sfen = "4k4/9/9/9/4r4/9/9/9/4K4 b GP 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    let mut mlist = MoveList::new();
+    mlist.generate_legal_drops(&pos, 0);
+    let mut drops = mlist
+        .slice(0)
+        .iter()
+        .map(|x| x.mv.to_usi_string())
+        .collect::<Vec<_>>();
+    drops.sort();
+    let mut expected = ["G*5f", "G*5g", "G*5h", "P*5f", "P*5g", "P*5h"]
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>();
+    expected.sort();
+    assert_eq!(drops, expected);
+    for ext_move in mlist.slice(0) {
+        assert!(pos.pseudo_legal::<NotSearchingType>(ext_move.mv) && pos.legal(ext_move.mv));
+    }
+
+    // double check. no drop can resolve it.
+    let sfen = "4k4/9/9/9/b3r4/9/9/9/4K4 b GP 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    assert_eq!(pos.checkers().count_ones(), 2);
+    mlist.generate_legal_drops(&pos, 0);
+    assert_eq!(mlist.size, 0);
+
+    // not in check. two pawns and dead squares are excluded.
+    let sfen = "4k4/9/9/9/9/9/P8/9/4K4 b NP 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    mlist.generate_legal_drops(&pos, 0);
+    let mut mlist_all = MoveList::new();
+    mlist_all.generate::<LegalType>(&pos, 0);
+    let mut drops = mlist.slice(0).iter().map(|x| x.mv).collect::<Vec<_>>();
+    let mut expected = mlist_all
+        .slice(0)
+        .iter()
+        .map(|x| x.mv)
+        .filter(|m| m.is_drop())
+        .collect::<Vec<_>>();
+    drops.sort_by_key(|m| m.to_u16());
+    expected.sort_by_key(|m| m.to_u16());
+    assert_eq!(drops, expected);
+    assert!(drops
+        .iter()
+        .all(|m| m.piece_type_dropped() != PieceType::PAWN || File::new(m.to()) != File::FILE9));
+    assert!(drops.iter().all(
+        |m| m.piece_type_dropped() != PieceType::KNIGHT || Rank::new(m.to()).0 > Rank::RANK2.0
+    ));
+}
+
+#[test]
+fn test_move_list_generate_legal_except() {
+    let pos = Position::new();
+    let mut mlist = MoveList::new();
+    mlist.generate::<LegalType>(&pos, 0);
+    let legal_num = mlist.size;
+    let excluded = Move::new_from_usi_str("7g7f", &pos).unwrap();
+    mlist.generate_legal_except(&pos, 0, excluded);
+    assert_eq!(mlist.size, legal_num - 1);
+    assert!(mlist.slice(0).iter().all(|x| x.mv != excluded));
+
+    // A move which isn't legal is just not generated.
+    let excluded = Move::new_unpromote(Square::SQ77, Square::SQ75, Piece::B_PAWN);
+    mlist.generate_legal_except(&pos, 0, excluded);
+    assert_eq!(mlist.size, legal_num);
+}
+
+#[test]
+fn test_generate_allow_drop_pawn_mate() {
+    // P*9b is mate. The same position as test_pawn_drop_mate().
+    let sfen = "kl7/1n7/K8/9/9/9/9/9/9 b P 1";
+    let mut pos = Position::new_from_sfen(sfen).unwrap();
+    let m = Move::new_drop(Piece::B_PAWN, Square::SQ92);
+    let contains = |pos: &Position| {
+        let mut mlist = MoveList::new();
+        mlist.generate::<LegalType>(pos, 0);
+        mlist.slice(0).iter().any(|x| x.mv == m)
+    };
+    assert!(!contains(&pos));
+    assert!(!pos.pseudo_legal::<NotSearchingType>(m));
+
+    pos.set_allow_drop_pawn_mate(true);
+    assert!(contains(&pos));
+    assert!(pos.pseudo_legal::<NotSearchingType>(m));
+    assert!(pos.legal_drop_targets(PieceType::PAWN).is_set(Square::SQ92));
+    assert_eq!(pos.pseudo_legal_reason(m), Ok(()));
+}
@@ -48,6 +48,10 @@ impl ButterflyHistory {
             }
         }
     }
+    #[cfg(test)]
+    pub fn is_cleared(&self) -> bool {
+        self.v.iter().flatten().all(|&x| x == 0)
+    }
 }
 
 pub struct CounterMoveHistory {
@@ -73,6 +77,10 @@ impl CounterMoveHistory {
             }
         }
     }
+    #[cfg(test)]
+    pub fn is_cleared(&self) -> bool {
+        self.v.iter().flatten().all(|x| x.is_none())
+    }
 }
 
 pub struct CapturePieceToHistory {
@@ -103,6 +111,10 @@ impl CapturePieceToHistory {
             }
         }
     }
+    #[cfg(test)]
+    pub fn is_cleared(&self) -> bool {
+        self.v.iter().flatten().flatten().all(|&x| x == 0)
+    }
 }
 
 pub struct PieceToHistory {
@@ -307,6 +319,23 @@ fn select_best_qcapture(
     None
 }
 
+fn select_best_good_qcapture(
+    list: &mut [ExtMove],
+    current_index: &mut usize,
+    tt_move: Option<Move>,
+    pos: &Position,
+    threshold: Value,
+) -> Option<Move> {
+    for i in 0..list.len() {
+        let m = pick_best(&mut list[i..]);
+        *current_index += 1;
+        if m != tt_move.unwrap_unchecked() && pos.see_ge(m, threshold) {
+            return Some(m);
+        }
+    }
+    None
+}
+
 fn select_best_qrecapture(
     list: &mut [ExtMove],
     current_index: &mut usize,
@@ -349,7 +378,7 @@ fn score_captures(
         let to = m.to();
         let pc_to = pos.piece_on(to);
         let pt_to = PieceType::new(pc_to);
-        ext_move.score = capture_piece_type_value(pt_to).0
+        ext_move.score = pos.piece_values().capture_piece_type_value(pt_to).0
             + unsafe { (*capture_history).get(m.piece_moved_after_move(), to, pt_to) };
     }
 }
@@ -357,7 +386,7 @@ fn score_captures(
 fn score_recaptures(move_list: &mut [ExtMove], pos: &Position) {
     for ext_move in move_list {
         let m = ext_move.mv;
-        ext_move.score = (capture_piece_value(pos.piece_on(m.to()))
+        ext_move.score = (pos.piece_values().capture_piece_value(pos.piece_on(m.to()))
             - lva_value(PieceType::new(m.piece_moved_before_move())))
         .0;
     }
@@ -391,7 +420,7 @@ fn score_evasion(
     for ext_move in move_list {
         let m = ext_move.mv;
         if m.is_capture(pos) {
-            ext_move.score = (capture_piece_value(pos.piece_on(m.to()))
+            ext_move.score = (pos.piece_values().capture_piece_value(pos.piece_on(m.to()))
                 - lva_value(PieceType::new(m.piece_moved_before_move())))
             .0;
         } else {
@@ -568,6 +597,7 @@ pub struct MovePickerForQSearch<'a> {
     recapture_square: Square,
     stage: StagesForQSearch,
     tt_move: Option<Move>,
+    capture_threshold: Option<Value>,
     move_list: MoveList,
 }
 
@@ -608,9 +638,32 @@ impl<'a> MovePickerForQSearch<'a> {
             recapture_square,
             stage,
             tt_move,
+            capture_threshold: None,
             move_list: MoveList::new(),
         }
     }
+    // Losing captures are skipped. Evasions are not filtered.
+    #[allow(dead_code)]
+    pub fn new_quiescence(
+        main_history: &ButterflyHistory,
+        capture_history: &CapturePieceToHistory,
+        continuation_history: &'a [*const PieceToHistory],
+        pos: &Position,
+        ttm: Option<Move>,
+    ) -> MovePickerForQSearch<'a> {
+        // recapture_square isn't used because depth > Depth::QS_RECAPTURES.
+        let mut mp = MovePickerForQSearch::new(
+            main_history,
+            capture_history,
+            continuation_history,
+            pos,
+            Square::SQ11,
+            ttm,
+            Depth::QS_CHECKS,
+        );
+        mp.capture_threshold = Some(Value::ZERO);
+        mp
+    }
 
     pub fn next_move(&mut self, pos: &Position) -> Option<Move> {
         loop {
@@ -634,11 +687,20 @@ impl<'a> MovePickerForQSearch<'a> {
                     self.stage = self.stage.next_variant().unwrap();
                 }
                 StagesForQSearch::QCapture => {
-                    let m = select_best_qcapture(
-                        self.move_list.slice_mut(self.cur),
-                        &mut self.cur,
-                        self.tt_move,
-                    );
+                    let m = match self.capture_threshold {
+                        Some(threshold) => select_best_good_qcapture(
+                            self.move_list.slice_mut(self.cur),
+                            &mut self.cur,
+                            self.tt_move,
+                            pos,
+                            threshold,
+                        ),
+                        None => select_best_qcapture(
+                            self.move_list.slice_mut(self.cur),
+                            &mut self.cur,
+                            self.tt_move,
+                        ),
+                    };
                     return m;
                     //if m != Move::NONE {
                     //    return m;
@@ -931,6 +993,91 @@ fn test_move_picker_for_main_search_next_move() {
     assert!(m.is_none());
 }
 
+#[test]
+fn test_move_picker_for_main_search_killer_and_history() {
+    let pos = Position::new();
+    let tt_move = Some(Move::new_unpromote(
+        Square::SQ77,
+        Square::SQ76,
+        Piece::B_PAWN,
+    ));
+    let killers = [
+        Some(Move::new_unpromote(
+            Square::SQ27,
+            Square::SQ26,
+            Piece::B_PAWN,
+        )),
+        None,
+    ];
+    let history_move = Move::new_unpromote(Square::SQ39, Square::SQ38, Piece::B_SILVER);
+    let mut mh = ButterflyHistory::new();
+    mh.update(Color::BLACK, history_move, 1000);
+    let cph = CapturePieceToHistory::new();
+    let ch = [
+        PieceToHistory::new(),
+        PieceToHistory::new(),
+        PieceToHistory::new(),
+        PieceToHistory::new(),
+        PieceToHistory::new(),
+        PieceToHistory::new(),
+    ];
+    let ch = ch
+        .iter()
+        .map(|x| x as *const PieceToHistory)
+        .collect::<Vec<_>>();
+    let skip_quiets = false;
+    let mut mp =
+        MovePickerForMainSearch::new(&pos, tt_move, Depth(5), &mh, &cph, &ch, None, &killers);
+    assert_eq!(mp.next_move(&pos, skip_quiets), tt_move); // MainTT
+    assert_eq!(mp.next_move(&pos, skip_quiets), killers[0]); // Refutation
+    assert_eq!(mp.next_move(&pos, skip_quiets), Some(history_move)); // Quiet
+    let mut moves_size = 3;
+    while let Some(m) = mp.next_move(&pos, skip_quiets) {
+        assert_ne!(m, tt_move.unwrap());
+        assert_ne!(m, killers[0].unwrap());
+        assert_ne!(m, history_move);
+        moves_size += 1;
+    }
+    let mut mlist = MoveList::new();
+    mlist.generate::<NonEvasionsType>(&pos, 0);
+    assert_eq!(moves_size, mlist.size);
+}
+
+#[test]
+fn test_move_picker_for_main_search_counter_move() {
+    let mut pos = Position::new();
+    let prev = Move::new_unpromote(Square::SQ77, Square::SQ76, Piece::B_PAWN);
+    let gives_check = pos.gives_check(prev);
+    pos.do_move(prev, gives_check);
+    let counter = Move::new_unpromote(Square::SQ33, Square::SQ34, Piece::W_PAWN);
+    let mut counter_moves = CounterMoveHistory::new();
+    let prev_sq = prev.to();
+    counter_moves.set(prev_sq, pos.piece_on(prev_sq), counter);
+    let cm = counter_moves.get(prev_sq, pos.piece_on(prev_sq));
+    assert_eq!(cm, Some(counter));
+    let mh = ButterflyHistory::new();
+    let cph = CapturePieceToHistory::new();
+    let ch = [
+        PieceToHistory::new(),
+        PieceToHistory::new(),
+        PieceToHistory::new(),
+        PieceToHistory::new(),
+        PieceToHistory::new(),
+        PieceToHistory::new(),
+    ];
+    let ch = ch
+        .iter()
+        .map(|x| x as *const PieceToHistory)
+        .collect::<Vec<_>>();
+    let killers = [None, None];
+    let skip_quiets = false;
+    let mut mp = MovePickerForMainSearch::new(&pos, None, Depth(5), &mh, &cph, &ch, cm, &killers);
+    assert_eq!(mp.next_move(&pos, skip_quiets), cm); // Refutation
+    while let Some(m) = mp.next_move(&pos, skip_quiets) {
+        assert_ne!(m, counter);
+    }
+}
+
 #[test]
 fn test_move_picker_for_main_search_next_move_evasion() {
     let sfen = "k8/9/9/5b3/6K2/l8/p8/1B7/9 b - 1";
@@ -1056,6 +1203,43 @@ fn test_move_picker_for_qsearch_next_move() {
     assert!(m.is_none());
 }
 
+#[test]
+fn test_move_picker_for_qsearch_new_quiescence() {
+    let sfen = "k8/9/9/5b3/9/l8/p8/1B7/1K7 b - 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    let mh = ButterflyHistory::new();
+    let cph = CapturePieceToHistory::new();
+    let ch = [
+        PieceToHistory::new(),
+        PieceToHistory::new(),
+        PieceToHistory::new(),
+        PieceToHistory::new(),
+    ];
+    let ch = ch
+        .iter()
+        .map(|x| x as *const PieceToHistory)
+        .collect::<Vec<_>>();
+    let mut mp = MovePickerForQSearch::new_quiescence(&mh, &cph, &ch, &pos, None);
+    let m = mp.next_move(&pos);
+    assert_eq!(
+        m.unwrap(),
+        Move::new_unpromote(Square::SQ88, Square::SQ44, Piece::B_BISHOP)
+    ); // Capture
+    let m = mp.next_move(&pos);
+    assert!(m.is_none()); // 88x97 loses the bishop for a pawn.
+
+    let sfen = "k8/9/9/5b3/6K2/l8/p8/1B7/9 b - 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    let mut mp = MovePickerForQSearch::new_quiescence(&mh, &cph, &ch, &pos, None);
+    let mut evasions = 0;
+    while mp.next_move(&pos).is_some() {
+        evasions += 1;
+    }
+    let mut mlist = MoveList::new();
+    mlist.generate::<EvasionsType>(&pos, 0);
+    assert_eq!(evasions, mlist.size);
+}
+
 #[test]
 fn test_move_picker_for_qsearch_next_move_evasion() {
     let sfen = "k8/9/9/5b3/6K2/l8/p8/1B7/9 b - 1";
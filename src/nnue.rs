@@ -0,0 +1,277 @@
+// HalfKP NNUE.
+// Input features are pairs of the king square and the EvalIndex of EvalList from each side's perspective.
+// Network file format (little endian):
+//   feature transformer biases:  i16 * HALF_DIMENSIONS
+//   feature transformer weights: i16 * HALF_DIMENSIONS * Square::NUM * EvalIndex::FE_END
+//   output bias:                 i32
+//   output weights:              i8 * HALF_DIMENSIONS * 2 (side to move, opponent)
+use crate::evaluate::*;
+use crate::movegen::*;
+use crate::position::*;
+use crate::search::*;
+use crate::types::*;
+
+pub const HALF_DIMENSIONS: usize = 256;
+const INPUT_DIMENSIONS: usize = Square::NUM * EvalIndex::FE_END.0;
+const OUTPUT_SCALE: i32 = 16;
+
+#[derive(Clone, Copy)]
+pub struct Accumulator {
+    v: [[i16; HALF_DIMENSIONS]; Color::NUM],
+}
+
+impl Accumulator {
+    pub fn new() -> Accumulator {
+        Accumulator {
+            v: [[0; HALF_DIMENSIONS]; Color::NUM],
+        }
+    }
+}
+
+pub struct Network {
+    biases: Vec<i16>,
+    weights: Vec<i16>,
+    output_bias: i32,
+    output_weights: Vec<i8>,
+}
+
+impl Network {
+    pub const BYTES: usize = HALF_DIMENSIONS * 2
+        + HALF_DIMENSIONS * INPUT_DIMENSIONS * 2
+        + 4
+        + HALF_DIMENSIONS * Color::NUM;
+
+    pub fn new_from_bytes(bytes: &[u8]) -> Result<Network, EvalLoadError> {
+        if bytes.len() != Network::BYTES {
+            return Err(EvalLoadError::SizeMismatch {
+                name: "NNUE".to_string(),
+                expected: Network::BYTES,
+                actual: bytes.len(),
+            });
+        }
+        let to_i16_vec = |bytes: &[u8]| {
+            bytes
+                .chunks_exact(2)
+                .map(|x| i16::from_le_bytes([x[0], x[1]]))
+                .collect::<Vec<i16>>()
+        };
+        let (biases, rest) = bytes.split_at(HALF_DIMENSIONS * 2);
+        let (weights, rest) = rest.split_at(HALF_DIMENSIONS * INPUT_DIMENSIONS * 2);
+        let (output_bias, output_weights) = rest.split_at(4);
+        Ok(Network {
+            biases: to_i16_vec(biases),
+            weights: to_i16_vec(weights),
+            output_bias: i32::from_le_bytes([
+                output_bias[0],
+                output_bias[1],
+                output_bias[2],
+                output_bias[3],
+            ]),
+            output_weights: output_weights.iter().map(|&x| x as i8).collect(),
+        })
+    }
+    fn king_square(pos: &Position, c: Color) -> Square {
+        if c == Color::BLACK {
+            pos.king_square(Color::BLACK)
+        } else {
+            pos.king_square(Color::WHITE).inverse()
+        }
+    }
+    fn feature_weights(&self, sq_k: Square, index: EvalIndex) -> &[i16] {
+        let begin = (sq_k.0 as usize * EvalIndex::FE_END.0 + index.0) * HALF_DIMENSIONS;
+        &self.weights[begin..begin + HALF_DIMENSIONS]
+    }
+    // The accumulator wraps around like the i16 SIMD arithmetic, so that it never panics in debug builds.
+    // A well trained network stays in the range anyway.
+    fn add_feature(&self, acc: &mut [i16; HALF_DIMENSIONS], sq_k: Square, index: EvalIndex) {
+        for (a, w) in acc.iter_mut().zip(self.feature_weights(sq_k, index)) {
+            *a = a.wrapping_add(*w);
+        }
+    }
+    fn sub_feature(&self, acc: &mut [i16; HALF_DIMENSIONS], sq_k: Square, index: EvalIndex) {
+        for (a, w) in acc.iter_mut().zip(self.feature_weights(sq_k, index)) {
+            *a = a.wrapping_sub(*w);
+        }
+    }
+    pub fn refresh(&self, pos: &Position, acc: &mut Accumulator) {
+        let list = pos.eval_list();
+        for &c in &[Color::BLACK, Color::WHITE] {
+            let sq_k = Network::king_square(pos, c);
+            let v = &mut acc.v[c.0 as usize];
+            v.copy_from_slice(&self.biases);
+            for i in 0..LIST_NUM {
+                self.add_feature(v, sq_k, list.get(i, c));
+            }
+        }
+    }
+    fn update_feature(
+        &self,
+        pos: &Position,
+        changed_eval_index: &ChangedEvalIndex,
+        acc: &mut Accumulator,
+    ) {
+        for &c in &[Color::BLACK, Color::WHITE] {
+            let sq_k = Network::king_square(pos, c);
+            let v = &mut acc.v[c.0 as usize];
+            let (old_index, new_index) = if c == Color::BLACK {
+                (changed_eval_index.old_index, changed_eval_index.new_index)
+            } else {
+                (
+                    changed_eval_index.old_index.inverse(),
+                    changed_eval_index.new_index.inverse(),
+                )
+            };
+            self.sub_feature(v, sq_k, old_index);
+            self.add_feature(v, sq_k, new_index);
+        }
+    }
+    // Must be called just after do_move() of a move which isn't a king move.
+    pub fn update(&self, pos: &Position, prev: &Accumulator, acc: &mut Accumulator) {
+        *acc = *prev;
+        self.update_feature(pos, &pos.changed_eval_index(), acc);
+        if pos.is_capture_after_move() {
+            self.update_feature(pos, &pos.changed_eval_index_captured(), acc);
+        }
+    }
+    pub fn output(&self, acc: &Accumulator, us: Color) -> Value {
+        let mut sum = self.output_bias;
+        for (i, &c) in [us, us.inverse()].iter().enumerate() {
+            let weights = &self.output_weights[i * HALF_DIMENSIONS..(i + 1) * HALF_DIMENSIONS];
+            for (&a, &w) in acc.v[c.0 as usize].iter().zip(weights) {
+                sum += i32::from(a).clamp(0, 127) * i32::from(w);
+            }
+        }
+        Value(sum / OUTPUT_SCALE)
+    }
+}
+
+static mut NETWORK: Option<Network> = None;
+
+pub fn load_nnue_from_bytes(bytes: &[u8]) -> Result<(), EvalLoadError> {
+    let network = Network::new_from_bytes(bytes)?;
+    unsafe {
+        NETWORK = Some(network);
+    }
+    Ok(())
+}
+
+pub fn load_nnue_file(path: &str) -> Result<(), String> {
+    let bytes = std::fs::read(path).map_err(|err| err.to_string())?;
+    load_nnue_from_bytes(&bytes).map_err(|err| err.to_string())
+}
+
+fn network() -> Option<&'static Network> {
+    unsafe { (*std::ptr::addr_of!(NETWORK)).as_ref() }
+}
+
+// Returns None if no network is loaded.
+pub fn evaluate_at_root(pos: &Position, stack: &mut [Stack]) -> Option<Value> {
+    let network = network()?;
+    let current = get_stack_mut(stack, 0);
+    network.refresh(pos, &mut current.accumulator);
+    current.static_eval_raw = EvalSum::new();
+    Some(network.output(&current.accumulator, pos.side_to_move()))
+}
+
+// Returns None if no network is loaded.
+pub fn evaluate(pos: &Position, stack: &mut [Stack]) -> Option<Value> {
+    let network = network()?;
+    if get_stack(stack, 0).static_eval_raw.is_not_evaluated() {
+        let prev = get_stack(stack, -1).accumulator;
+        let last_move = get_stack(stack, -1).current_move.unwrap_unchecked();
+        debug_assert!(last_move != Move::NULL);
+        let current = get_stack_mut(stack, 0);
+        if PieceType::new(last_move.piece_moved_before_move()) == PieceType::KING {
+            network.refresh(pos, &mut current.accumulator);
+        } else {
+            network.update(pos, &prev, &mut current.accumulator);
+        }
+        current.static_eval_raw = EvalSum::new();
+    }
+    Some(network.output(&get_stack(stack, 0).accumulator, pos.side_to_move()))
+}
+
+#[cfg(test)]
+fn random_network_bytes(seed: u64) -> Vec<u8> {
+    let mut x = seed;
+    let mut next = || {
+        // xorshift64
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        // small weights to avoid i16 overflow.
+        (x % 16) as i16 - 8
+    };
+    let mut bytes = vec![];
+    for _ in 0..HALF_DIMENSIONS * (1 + INPUT_DIMENSIONS) {
+        bytes.extend_from_slice(&next().to_le_bytes());
+    }
+    bytes.extend_from_slice(&0i32.to_le_bytes());
+    for _ in 0..HALF_DIMENSIONS * Color::NUM {
+        bytes.push(next() as i8 as u8);
+    }
+    bytes
+}
+
+#[test]
+fn test_network_new_from_bytes() {
+    assert!(Network::new_from_bytes(&[]).is_err());
+    let bytes = random_network_bytes(1);
+    assert!(Network::new_from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    assert!(Network::new_from_bytes(&bytes).is_ok());
+}
+
+#[test]
+fn test_network_evaluate() {
+    let bytes = random_network_bytes(2);
+    let network0 = Network::new_from_bytes(&bytes).unwrap();
+    let network1 = Network::new_from_bytes(&bytes).unwrap();
+    let mut pos = Position::new();
+    let mut acc0 = Accumulator::new();
+    let mut acc1 = Accumulator::new();
+    network0.refresh(&pos, &mut acc0);
+    network1.refresh(&pos, &mut acc1);
+    let value = network0.output(&acc0, pos.side_to_move());
+    assert!(value.0.abs() < Value::MATE_IN_MAX_PLY.0);
+    assert_eq!(value, network1.output(&acc1, pos.side_to_move()));
+
+    // incremental update == refresh
+    for usi_str in &[
+        "7g7f", "3c3d", "8h2b+", "3a2b", "5i6h", "B*4e", "6h7h", "4e2g+",
+    ] {
+        let m = Move::new_from_usi_str(usi_str, &pos).unwrap();
+        let gives_check = pos.gives_check(m);
+        pos.do_move(m, gives_check);
+        let prev = acc0;
+        if PieceType::new(m.piece_moved_before_move()) == PieceType::KING {
+            network0.refresh(&pos, &mut acc0);
+        } else {
+            network0.update(&pos, &prev, &mut acc0);
+        }
+        network1.refresh(&pos, &mut acc1);
+        assert_eq!(
+            network0.output(&acc0, pos.side_to_move()),
+            network1.output(&acc1, pos.side_to_move())
+        );
+    }
+}
+
+#[test]
+fn test_network_accumulator_overflow() {
+    // Every weight is large, so the accumulator overflows.
+    let network = Network::new_from_bytes(&vec![0x7f; Network::BYTES]).unwrap();
+    let mut pos = Position::new();
+    let mut acc = Accumulator::new();
+    network.refresh(&pos, &mut acc);
+    let m = Move::new_from_usi_str("7g7f", &pos).unwrap();
+    let gives_check = pos.gives_check(m);
+    pos.do_move(m, gives_check);
+    let prev = acc;
+    network.update(&pos, &prev, &mut acc);
+    let mut refreshed = Accumulator::new();
+    network.refresh(&pos, &mut refreshed);
+    assert_eq!(
+        network.output(&acc, pos.side_to_move()),
+        network.output(&refreshed, pos.side_to_move())
+    );
+}
@@ -55,6 +55,7 @@ pub fn piece_value(pc: Piece) -> Value {
     debug_assert!((pc.0 as usize) < Piece::NUM);
     unsafe { Value(*PIECE_VALUES.get_unchecked(pc.0 as usize)) }
 }
+#[allow(dead_code)]
 pub fn piece_type_value(pt: PieceType) -> Value {
     debug_assert!(0 <= pt.0);
     debug_assert!((pt.0 as usize) < PieceType::NUM);
@@ -110,11 +111,13 @@ const CAPTURE_PIECE_VALUES: [i32; Piece::NUM] = [
     CAPTURE_DRAGON_VALUE,
 ];
 
+#[allow(dead_code)]
 pub fn capture_piece_value(pc: Piece) -> Value {
     debug_assert!(0 <= pc.0);
     debug_assert!((pc.0 as usize) < Piece::NUM);
     unsafe { Value(*CAPTURE_PIECE_VALUES.get_unchecked(pc.0 as usize)) }
 }
+#[allow(dead_code)]
 pub fn capture_piece_type_value(pt: PieceType) -> Value {
     debug_assert!(0 <= pt.0);
     debug_assert!((pt.0 as usize) < PieceType::NUM);
@@ -138,6 +141,7 @@ const PROMOTE_PIECE_VALUES: [i32; 7] = [
     PROMOTE_ROOK_VALUE,
 ];
 
+#[allow(dead_code)]
 pub fn promote_piece_type_value(pt: PieceType) -> Value {
     debug_assert!(0 <= pt.0);
     debug_assert!((pt.0 as usize) < PROMOTE_PIECE_VALUES.len());
@@ -164,6 +168,40 @@ pub fn lva_value(pt: PieceType) -> Value {
     }
 }
 
+// Piece values which can be changed at runtime. Each Position holds its own copy.
+#[derive(Clone, Copy)]
+pub struct PieceValues {
+    values: [i32; PieceType::NUM],
+}
+
+impl PieceValues {
+    pub fn new() -> PieceValues {
+        let mut values = [0; PieceType::NUM];
+        values.copy_from_slice(&PIECE_VALUES[..PieceType::NUM]);
+        PieceValues { values }
+    }
+    #[allow(dead_code)]
+    pub fn set_piece_type_value(&mut self, pt: PieceType, v: Value) {
+        debug_assert!(pt != PieceType::KING); // see_ge() needs that KING_VALUE == 0.
+        self.values[pt.0 as usize] = v.0;
+    }
+    pub fn piece_type_value(&self, pt: PieceType) -> Value {
+        Value(self.values[pt.0 as usize])
+    }
+    pub fn capture_piece_type_value(&self, pt: PieceType) -> Value {
+        match pt {
+            PieceType::OCCUPIED | PieceType::KING => Value::ZERO,
+            _ => self.piece_type_value(pt) + self.piece_type_value(pt.to_demote_if_possible()),
+        }
+    }
+    pub fn capture_piece_value(&self, pc: Piece) -> Value {
+        self.capture_piece_type_value(PieceType::new(pc))
+    }
+    pub fn promote_piece_type_value(&self, pt: PieceType) -> Value {
+        self.piece_type_value(pt.to_promote()) - self.piece_type_value(pt)
+    }
+}
+
 #[test]
 fn test_piece_value() {
     assert_eq!(piece_type_value(PieceType::PAWN), Value(PAWN_VALUE));
@@ -424,3 +462,39 @@ fn test_promote_piece_value() {
         Value(PROMOTE_ROOK_VALUE)
     );
 }
+
+#[test]
+fn test_piece_values() {
+    let mut pv = PieceValues::new();
+    for pt in (PieceType::PAWN.0..=PieceType::DRAGON.0).map(PieceType) {
+        assert_eq!(pv.piece_type_value(pt), piece_type_value(pt));
+        assert_eq!(
+            pv.capture_piece_type_value(pt),
+            capture_piece_type_value(pt)
+        );
+        if pt.is_promotable() {
+            assert_eq!(
+                pv.promote_piece_type_value(pt),
+                promote_piece_type_value(pt)
+            );
+        }
+    }
+    assert_eq!(pv.capture_piece_type_value(PieceType::KING), Value::ZERO);
+    assert_eq!(pv.capture_piece_value(Piece::EMPTY), Value::ZERO);
+    assert_eq!(
+        pv.capture_piece_value(Piece::W_HORSE),
+        capture_piece_value(Piece::W_HORSE)
+    );
+
+    pv.set_piece_type_value(PieceType::ROOK, Value(2000));
+    assert_eq!(pv.piece_type_value(PieceType::ROOK), Value(2000));
+    assert_eq!(pv.capture_piece_type_value(PieceType::ROOK), Value(4000));
+    assert_eq!(
+        pv.capture_piece_type_value(PieceType::DRAGON),
+        Value(DRAGON_VALUE + 2000)
+    );
+    assert_eq!(
+        pv.promote_piece_type_value(PieceType::ROOK),
+        Value(DRAGON_VALUE - 2000)
+    );
+}
@@ -35,6 +35,65 @@ pub enum Repetition {
     Inferior,
 }
 
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RepetitionKind {
+    Draw,
+    PerpetualCheck,
+    SuperiorHand,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameResult {
+    BlackWin,
+    WhiteWin,
+    Draw,
+}
+
+impl GameResult {
+    fn win(c: Color) -> GameResult {
+        if c == Color::BLACK {
+            GameResult::BlackWin
+        } else {
+            GameResult::WhiteWin
+        }
+    }
+}
+
+// The difference between two positions.
+// squares: (square, piece before, piece after) for each square whose piece changed.
+// hands: (color, piece type, after - before) for each hand count which changed.
+#[allow(dead_code)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PositionDiff {
+    pub squares: Vec<(Square, Piece, Piece)>,
+    pub hands: Vec<(Color, PieceType, i32)>,
+}
+
+#[allow(dead_code)]
+impl PositionDiff {
+    pub fn is_empty(&self) -> bool {
+        self.squares.is_empty() && self.hands.is_empty()
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IllegalReason {
+    WrongSideToMove,
+    NoPieceOnFrom,
+    TargetOccupiedBySelf,
+    NotAttackSquare,
+    DropNoPieceInHand,
+    DropOnOccupied,
+    DropOnDeadSquare,
+    Nifu,
+    DropPawnMate,
+    IllegalPromotion,
+    NotEvasion,
+}
+
 #[derive(Clone)]
 pub struct CheckInfo {
     blockers_and_pinners_for_king: [(Bitboard, Bitboard); Color::NUM], // color is color_of_king
@@ -443,7 +502,7 @@ impl<'a> BitStreamWriter<'a> {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct EvalList(pub [[EvalIndex; 2]; LIST_NUM]);
 
 impl EvalList {
@@ -557,6 +616,7 @@ pub struct StateInfo {
     check_info: CheckInfo,
     changed_eval_index: ChangedEvalIndex,
     changed_eval_index_captured: ChangedEvalIndex,
+    last_move: Option<Move>, // None after a null move.
 }
 
 impl StateInfo {
@@ -573,6 +633,7 @@ impl StateInfo {
             check_info: CheckInfo::ZERO,
             changed_eval_index: ChangedEvalIndex::ZERO,
             changed_eval_index_captured: ChangedEvalIndex::ZERO,
+            last_move: None,
         }
     }
     unsafe fn new_from_old_state(old_state: &StateInfo) -> StateInfo {
@@ -588,14 +649,15 @@ impl StateInfo {
             check_info: std::mem::uninitialized(),
             changed_eval_index: std::mem::uninitialized(),
             changed_eval_index_captured: std::mem::uninitialized(),
+            last_move: None,
         }
     }
-    fn new_from_position(pos: &PositionBase) -> StateInfo {
+    fn new_from_position(pos: &PositionBase, piece_values: &PieceValues) -> StateInfo {
         let us = pos.side_to_move();
         let them = us.inverse();
         let king_sq = pos.king_square(us);
         StateInfo {
-            material: StateInfo::new_material(pos),
+            material: StateInfo::new_material(pos, piece_values),
             plies_from_null: 0,
             continuous_checks: [0, 0],
             board_key: StateInfo::new_board_key(pos),
@@ -606,9 +668,14 @@ impl StateInfo {
             check_info: CheckInfo::new(&pos),
             changed_eval_index: ChangedEvalIndex::ZERO,
             changed_eval_index_captured: ChangedEvalIndex::ZERO,
+            last_move: None,
         }
     }
-    fn new_material(pos: &PositionBase) -> Value {
+    fn new_material(pos: &PositionBase, piece_values: &PieceValues) -> Value {
+        StateInfo::new_board_material(pos, piece_values)
+            + StateInfo::new_hand_material(pos, piece_values)
+    }
+    fn new_board_material(pos: &PositionBase, piece_values: &PieceValues) -> Value {
         let mut val = Value(0);
         for &pt in [
             PieceType::PAWN,
@@ -629,11 +696,15 @@ impl StateInfo {
         {
             let num = pos.pieces_cp(Color::BLACK, pt).count_ones() as i32
                 - pos.pieces_cp(Color::WHITE, pt).count_ones() as i32;
-            val += Value(num * piece_type_value(pt).0);
+            val += Value(num * piece_values.piece_type_value(pt).0);
         }
+        val
+    }
+    fn new_hand_material(pos: &PositionBase, piece_values: &PieceValues) -> Value {
+        let mut val = Value(0);
         for &pt in PieceType::ALL_HAND.iter() {
             let num = pos.hand(Color::BLACK).num(pt) as i32 - pos.hand(Color::WHITE).num(pt) as i32;
-            val += Value(num * piece_type_value(pt).0);
+            val += Value(num * piece_values.piece_type_value(pt).0);
         }
         val
     }
@@ -687,7 +758,54 @@ impl StateInfo {
         check_info: CheckInfo::ZERO,
         changed_eval_index: ChangedEvalIndex::ZERO,
         changed_eval_index_captured: ChangedEvalIndex::ZERO,
+        last_move: None,
+    };
+}
+
+// Board dimensions of shogi variants.
+// A smaller board is placed on the upper right corner of the 9x9 board. (e.g. 5x5 uses 5a-1e.)
+// Only PositionBase (sfen) supports it yet. Position, move generation and evaluation are for STANDARD.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BoardGeometry {
+    pub files: usize,
+    pub ranks: usize,
+    pub promotion_ranks: usize,
+    max_pieces: [i64; 8], // index: PieceType::PAWN ... PieceType::GOLD
+}
+
+impl BoardGeometry {
+    pub const STANDARD: BoardGeometry = BoardGeometry {
+        files: File::NUM,
+        ranks: Rank::NUM,
+        promotion_ranks: 3,
+        max_pieces: [0, 18, 4, 4, 4, 2, 2, 4],
     };
+    #[allow(dead_code)]
+    pub const MINISHOGI: BoardGeometry = BoardGeometry {
+        files: 5,
+        ranks: 5,
+        promotion_ranks: 1,
+        max_pieces: [0, 2, 0, 0, 2, 2, 2, 2],
+    };
+    pub fn files_from_left(&self) -> &'static [File] {
+        &File::ALL_FROM_LEFT[File::NUM - self.files..]
+    }
+    pub fn ranks_from_upper(&self) -> &'static [Rank] {
+        &Rank::ALL_FROM_UPPER[..self.ranks]
+    }
+    // The number of pieces of pt and its promoted piece type.
+    pub fn max_pieces(&self, pt: PieceType) -> i64 {
+        self.max_pieces[pt.to_demote_if_possible().0 as usize]
+    }
+    #[allow(dead_code)]
+    pub fn is_opponent_field(&self, c: Color, r: Rank) -> bool {
+        let r = r.0 as usize;
+        if c == Color::BLACK {
+            r < self.promotion_ranks
+        } else {
+            self.ranks - self.promotion_ranks <= r && r < self.ranks
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -700,10 +818,19 @@ pub struct PositionBase {
     game_ply: i32,
     king_squares: [Square; Color::NUM],
     side_to_move: Color,
+    geometry: BoardGeometry,
+    // relaxed rule for variants and training data. false in the standard rules.
+    allow_drop_pawn_mate: bool,
 }
 
 impl PositionBase {
     pub fn new_from_sfen_args(sfen_slice: &[&str]) -> Result<PositionBase, SfenError> {
+        Self::new_from_sfen_args_with_geometry(sfen_slice, BoardGeometry::STANDARD)
+    }
+    pub fn new_from_sfen_args_with_geometry(
+        sfen_slice: &[&str],
+        geometry: BoardGeometry,
+    ) -> Result<PositionBase, SfenError> {
         if sfen_slice.len() < 4 {
             return Err(SfenError::InvalidNumberOfSections {
                 sections: sfen_slice.len(),
@@ -722,26 +849,29 @@ impl PositionBase {
             game_ply: 0,
             king_squares: [Square(0), Square(0)],
             side_to_move: Color::BLACK,
+            geometry,
+            allow_drop_pawn_mate: false,
         };
         let rank_str_vec: Vec<&str> = board_str.split('/').collect();
-        if rank_str_vec.len() != Rank::NUM {
+        if rank_str_vec.len() != geometry.ranks {
             return Err(SfenError::InvalidNumberOfRanks {
                 ranks: rank_str_vec.len(),
             });
         }
-        for (rank_idx, rank) in Rank::ALL_FROM_UPPER.iter().enumerate() {
+        let files = geometry.files_from_left();
+        for (rank_idx, rank) in geometry.ranks_from_upper().iter().enumerate() {
             let rank_str = rank_str_vec[rank_idx as usize];
             let mut file_idx: usize = 0;
             let re = regex::Regex::new(r"(\d+|\+?[[:alpha:]])").unwrap();
             for cap in re.captures_iter(rank_str) {
-                if file_idx >= File::NUM {
+                if file_idx >= files.len() {
                     return Err(SfenError::InvalidNumberOfFiles { files: file_idx });
                 }
                 let token: &str = &cap[0];
                 if let Ok(digit) = token.to_string().parse::<i64>() {
                     if digit <= 0
-                        || (Rank::NUM as i64) < digit
-                        || (Rank::NUM as i64) < (file_idx as i64) + digit
+                        || (files.len() as i64) < digit
+                        || (files.len() as i64) < (file_idx as i64) + digit
                     {
                         return Err(SfenError::InvalidNumberOfEmptySquares {
                             empty_squares: digit,
@@ -750,7 +880,7 @@ impl PositionBase {
                     file_idx += digit as usize;
                 } else if let Some(pc) = Piece::new_from_str(token) {
                     let pt = PieceType::new(pc);
-                    let sq = Square::new(File::ALL_FROM_LEFT[file_idx], *rank);
+                    let sq = Square::new(files[file_idx], *rank);
                     let c = Color::new(pc);
                     pos.board[sq.0 as usize] = pc;
                     pos.by_type_bb[PieceType::OCCUPIED.0 as usize].set(sq);
@@ -783,7 +913,8 @@ impl PositionBase {
         }
         if hands_str != "-" {
             let mut hand_num: i64 = 1;
-            let re = regex::Regex::new(r"(\d+|[[:alpha:]])").unwrap();
+            // Matches "+P" too, so that promoted pieces are rejected rather than skipping "+".
+            let re = regex::Regex::new(r"(\d+|\+?[[:alpha:]])").unwrap();
             for cap in re.captures_iter(hands_str) {
                 let token: &str = &cap[0];
                 if let Ok(digit) = token.to_string().parse::<i64>() {
@@ -791,29 +922,34 @@ impl PositionBase {
                         return Err(SfenError::InvalidNumberOfHandPieces { number: digit });
                     }
                     hand_num = digit;
+                } else if token.starts_with('+') {
+                    return Err(SfenError::PromotedPieceInHand {
+                        chars: token.to_string(),
+                    });
                 } else if let Some(pc) = Piece::new_hand_piece_from_str(token) {
                     let pt = PieceType::new(pc);
                     let c = Color::new(pc);
+                    let max = geometry.max_pieces(pt);
                     match pt {
-                        PieceType::PAWN if 18 < hand_num => {
+                        PieceType::PAWN if max < hand_num => {
                             return Err(SfenError::InvalidNumberOfPawns { number: hand_num });
                         }
-                        PieceType::LANCE if 4 < hand_num => {
+                        PieceType::LANCE if max < hand_num => {
                             return Err(SfenError::InvalidNumberOfLances { number: hand_num });
                         }
-                        PieceType::KNIGHT if 4 < hand_num => {
+                        PieceType::KNIGHT if max < hand_num => {
                             return Err(SfenError::InvalidNumberOfKnights { number: hand_num });
                         }
-                        PieceType::SILVER if 4 < hand_num => {
+                        PieceType::SILVER if max < hand_num => {
                             return Err(SfenError::InvalidNumberOfSilvers { number: hand_num });
                         }
-                        PieceType::GOLD if 4 < hand_num => {
+                        PieceType::GOLD if max < hand_num => {
                             return Err(SfenError::InvalidNumberOfGolds { number: hand_num });
                         }
-                        PieceType::BISHOP if 2 < hand_num => {
+                        PieceType::BISHOP if max < hand_num => {
                             return Err(SfenError::InvalidNumberOfBishops { number: hand_num });
                         }
-                        PieceType::ROOK if 2 < hand_num => {
+                        PieceType::ROOK if max < hand_num => {
                             return Err(SfenError::InvalidNumberOfRooks { number: hand_num });
                         }
                         _ => {
@@ -844,7 +980,8 @@ impl PositionBase {
                 });
             }
         }
-        fn check_pieces(pos: &PositionBase, pts: &[PieceType], max: i64) -> Result<(), SfenError> {
+        fn check_pieces(pos: &PositionBase, pts: &[PieceType]) -> Result<(), SfenError> {
+            let max = pos.geometry.max_pieces(pts[0]);
             let number = i64::from(
                 pts.iter()
                     .fold(0, |sum, &pt| sum + pos.pieces_p(pt).count_ones())
@@ -865,19 +1002,20 @@ impl PositionBase {
                 }
             }
         }
-        check_pieces(&pos, &[PieceType::PAWN, PieceType::PRO_PAWN], 18)?;
-        check_pieces(&pos, &[PieceType::LANCE, PieceType::PRO_LANCE], 4)?;
-        check_pieces(&pos, &[PieceType::KNIGHT, PieceType::PRO_KNIGHT], 4)?;
-        check_pieces(&pos, &[PieceType::SILVER, PieceType::PRO_SILVER], 4)?;
-        check_pieces(&pos, &[PieceType::GOLD], 4)?;
-        check_pieces(&pos, &[PieceType::BISHOP, PieceType::HORSE], 2)?;
-        check_pieces(&pos, &[PieceType::ROOK, PieceType::DRAGON], 2)?;
+        check_pieces(&pos, &[PieceType::PAWN, PieceType::PRO_PAWN])?;
+        check_pieces(&pos, &[PieceType::LANCE, PieceType::PRO_LANCE])?;
+        check_pieces(&pos, &[PieceType::KNIGHT, PieceType::PRO_KNIGHT])?;
+        check_pieces(&pos, &[PieceType::SILVER, PieceType::PRO_SILVER])?;
+        check_pieces(&pos, &[PieceType::GOLD])?;
+        check_pieces(&pos, &[PieceType::BISHOP, PieceType::HORSE])?;
+        check_pieces(&pos, &[PieceType::ROOK, PieceType::DRAGON])?;
         Ok(pos)
     }
     pub fn new_from_huffman_coded_position(
         hcp: &HuffmanCodedPosition,
     ) -> Result<PositionBase, u32> {
         let mut bs = BitStreamReader::new(&hcp.buf);
+        let geometry = BoardGeometry::STANDARD;
         let mut pos = PositionBase {
             board: [Piece::EMPTY; Square::NUM],
             by_type_bb: [Bitboard::ZERO; PieceType::NUM],
@@ -887,6 +1025,8 @@ impl PositionBase {
             game_ply: 0,
             king_squares: [Square(0), Square(0)],
             side_to_move: Color::BLACK,
+            geometry,
+            allow_drop_pawn_mate: false,
         };
         pos.side_to_move = Color(i32::from(bs.get_bit_from_lsb()));
         pos.king_squares[Color::BLACK.0 as usize] = {
@@ -1037,6 +1177,10 @@ impl PositionBase {
     pub fn side_to_move(&self) -> Color {
         self.side_to_move
     }
+    #[allow(dead_code)]
+    pub fn geometry(&self) -> BoardGeometry {
+        self.geometry
+    }
     pub fn king_square(&self, c: Color) -> Square {
         debug_assert!((c.0 as usize) < Color::NUM);
         unsafe { *self.king_squares.get_unchecked(c.0 as usize) }
@@ -1052,6 +1196,9 @@ impl PositionBase {
             self.by_type_bb.get_unchecked_mut(pt.0 as usize).xor(sq);
             self.by_color_bb.get_unchecked_mut(c.0 as usize).xor(sq);
         }
+        if pt.is_gold_moving() {
+            self.golds_bb.xor(sq);
+        }
     }
     fn put_piece(&mut self, pc: Piece, sq: Square) {
         debug_assert!(!self.pieces_p(PieceType::new(pc)).is_set(sq));
@@ -1092,6 +1239,12 @@ impl PositionBase {
                 .xor(sq);
             *self.board.get_unchecked_mut(sq.0 as usize) = pc_new;
         }
+        if pt_old.is_gold_moving() {
+            self.golds_bb.xor(sq);
+        }
+        if pt_new.is_gold_moving() {
+            self.golds_bb.xor(sq);
+        }
         debug_assert!(self.pieces_p(pt_new).is_set(sq));
         debug_assert!(self.pieces_c(color_new).is_set(sq));
     }
@@ -1251,17 +1404,36 @@ impl PositionBase {
         };
         s
     }
+    #[allow(dead_code)]
     pub fn print(&self) {
         println!("{}", self.to_csa_string());
     }
     pub fn to_sfen(&self) -> String {
+        format!("{} {}", self.position_signature(), self.game_ply)
+    }
+    // SFEN without the ply field.
+    pub fn position_signature(&self) -> String {
+        let side_to_move = match self.side_to_move {
+            Color::BLACK => "b",
+            Color::WHITE => "w",
+            _ => unreachable!(),
+        };
+        format!(
+            "{} {} {}",
+            self.board_sfen(),
+            side_to_move,
+            self.hands_sfen()
+        )
+    }
+    // The board field of SFEN.
+    pub fn board_sfen(&self) -> String {
         let mut s = "".to_string();
-        for rank in Rank::ALL_FROM_UPPER.iter() {
+        for rank in self.geometry.ranks_from_upper().iter() {
             let mut empty_squares = 0;
             if s != "" {
                 s += "/";
             }
-            for file in File::ALL_FROM_LEFT.iter() {
+            for file in self.geometry.files_from_left().iter() {
                 let sq = Square::new(*file, *rank);
                 let pc = self.piece_on(sq);
                 if pc == Piece::EMPTY {
@@ -1278,29 +1450,26 @@ impl PositionBase {
                 s += &empty_squares.to_string();
             }
         }
-        match self.side_to_move {
-            Color::BLACK => s += " b ",
-            Color::WHITE => s += " w ",
-            _ => unreachable!(),
-        }
+        s
+    }
+    // The hands field of SFEN. "-" if both hands are empty.
+    pub fn hands_sfen(&self) -> String {
         if self.hand(Color::BLACK).0 == 0 && self.hand(Color::WHITE).0 == 0 {
-            s += "-";
-        } else {
-            for c in Color::ALL_FROM_BLACK.iter() {
-                for pt in PieceType::ALL_HAND_FOR_SFEN.iter() {
-                    let num = self.hand(*c).num(*pt);
-                    if 2 <= num {
-                        s += &num.to_string();
-                    }
-                    if num != 0 {
-                        let pc = Piece::new(*c, *pt);
-                        s += &pc.to_usi_str();
-                    }
+            return "-".to_string();
+        }
+        let mut s = "".to_string();
+        for c in Color::ALL_FROM_BLACK.iter() {
+            for pt in PieceType::ALL_HAND_FOR_SFEN.iter() {
+                let num = self.hand(*c).num(*pt);
+                if 2 <= num {
+                    s += &num.to_string();
+                }
+                if num != 0 {
+                    let pc = Piece::new(*c, *pt);
+                    s += &pc.to_usi_str();
                 }
             }
         }
-        s += " ";
-        s += &self.game_ply.to_string();
         s
     }
 }
@@ -1311,19 +1480,130 @@ pub struct Position {
     eval_index_to_eval_list_index: EvalIndexToEvalListIndex,
     states: Vec<StateInfo>,
     nodes: Arc<AtomicI64>,
+    piece_values: PieceValues,
+}
+
+// "Same position" semantics. Only the board, hands and side to move are compared.
+// The move history (states), game ply and node counter are ignored.
+impl PartialEq for Position {
+    fn eq(&self, other: &Position) -> bool {
+        self.key() == other.key()
+            && self.side_to_move() == other.side_to_move()
+            && self.base.hands == other.base.hands
+            && self.base.board[..] == other.base.board[..]
+    }
+}
+
+impl Eq for Position {}
+
+// Legal moves which are generated only once until the position changes.
+// The caller owns this beside the Position, so do_move() and undo_move() don't have to clear it.
+// Instead, any change of the board, the hands or the side to move changes the key.
+// This assumes that the 64 bit keys of two positions which are queried in a row don't collide,
+// as the TT does. If they did, the moves of the other position would be returned.
+pub struct LegalMovesCache {
+    tag: Option<(Key, bool)>, // (key, allow_drop_pawn_mate)
+    moves: Vec<Move>,
+}
+
+impl LegalMovesCache {
+    #[allow(dead_code)]
+    pub fn new() -> LegalMovesCache {
+        LegalMovesCache {
+            tag: None,
+            moves: Vec::new(),
+        }
+    }
+    #[allow(dead_code)]
+    pub fn legal_moves(&mut self, pos: &Position) -> &[Move] {
+        let tag = Some((pos.key(), pos.allow_drop_pawn_mate()));
+        if self.tag != tag {
+            let mut mlist = MoveList::new();
+            mlist.generate::<LegalType>(pos, 0);
+            self.moves.clear();
+            self.moves.extend(mlist.slice(0).iter().map(|x| x.mv));
+            self.tag = tag;
+        }
+        &self.moves
+    }
+}
+
+// Undoes the move when it is dropped. The position is reachable through the guard meanwhile.
+pub struct MoveGuard<'a> {
+    pos: &'a mut Position,
+    m: Move,
+}
+
+impl<'a> std::ops::Deref for MoveGuard<'a> {
+    type Target = Position;
+    fn deref(&self) -> &Position {
+        self.pos
+    }
+}
+
+impl<'a> std::ops::DerefMut for MoveGuard<'a> {
+    fn deref_mut(&mut self) -> &mut Position {
+        self.pos
+    }
+}
+
+impl<'a> Drop for MoveGuard<'a> {
+    fn drop(&mut self) {
+        self.pos.undo_move(self.m);
+    }
+}
+
+impl std::fmt::Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "{}", self.to_csa_string())?;
+        write!(f, "key: {}", self.key().0)
+    }
+}
+
+impl std::hash::Hash for Position {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.key().0.hash(state);
+    }
 }
 
 impl Position {
     pub fn new() -> Position {
         Position::new_from_sfen(START_SFEN).unwrap()
     }
+    #[allow(dead_code)]
+    pub fn handicap(kind: Handicap) -> Position {
+        Position::new_from_sfen(kind.to_sfen()).unwrap()
+    }
+    // The start position followed by the USI moves.
+    #[allow(dead_code)]
+    pub fn from_usi_moves(moves: &[&str]) -> Result<Position, SfenError> {
+        let mut pos = Position::new();
+        pos.apply_usi_moves(moves).map_err(|(_, err)| err)?;
+        Ok(pos)
+    }
+    // On error, returns the index of the first move that failed. The moves before it are done.
+    pub fn apply_usi_moves(&mut self, moves: &[&str]) -> Result<(), (usize, SfenError)> {
+        for (i, move_str) in moves.iter().enumerate() {
+            let m = Move::new_from_usi_str(move_str, self).ok_or_else(|| {
+                (
+                    i,
+                    SfenError::IllegalMove {
+                        move_str: move_str.to_string(),
+                    },
+                )
+            })?;
+            self.do_move_auto(m);
+        }
+        Ok(())
+    }
     pub fn new_from_sfen(sfen: &str) -> Result<Position, SfenError> {
         Self::new_from_sfen_args(sfen.split_whitespace().collect::<Vec<&str>>().as_slice())
     }
     pub fn new_from_sfen_args(sfen_slice: &[&str]) -> Result<Position, SfenError> {
         match PositionBase::new_from_sfen_args(sfen_slice) {
             Ok(base) => {
-                let state = StateInfo::new_from_position(&base);
+                let piece_values = PieceValues::new();
+                let state = StateInfo::new_from_position(&base, &piece_values);
                 let eval_list = EvalList::new(&base);
                 let eval_index_to_eval_list_index = EvalIndexToEvalListIndex::new(&eval_list);
                 let mut pos = Position {
@@ -1332,6 +1612,7 @@ impl Position {
                     eval_index_to_eval_list_index,
                     states: Vec::new(),
                     nodes: Arc::new(AtomicI64::new(0)),
+                    piece_values,
                 };
                 pos.init_states_and_push(state);
                 debug_assert!(pos.is_ok());
@@ -1340,10 +1621,26 @@ impl Position {
             Err(sfen_error) => Err(sfen_error),
         }
     }
+    // Same as new_from_sfen(), but reuses the allocated states of self.
+    // self isn't changed if sfen is invalid.
+    #[allow(dead_code)]
+    pub fn set_from_sfen(&mut self, sfen: &str) -> Result<(), SfenError> {
+        let base = PositionBase::new_from_sfen_args(
+            sfen.split_whitespace().collect::<Vec<&str>>().as_slice(),
+        )?;
+        let state = StateInfo::new_from_position(&base, &self.piece_values);
+        self.eval_list = EvalList::new(&base);
+        self.eval_index_to_eval_list_index = EvalIndexToEvalListIndex::new(&self.eval_list);
+        self.base = base;
+        self.init_states_and_push(state);
+        debug_assert!(self.is_ok());
+        Ok(())
+    }
     pub fn new_from_huffman_coded_position(hcp: &HuffmanCodedPosition) -> Result<Position, u32> {
         match PositionBase::new_from_huffman_coded_position(hcp) {
             Ok(base) => {
-                let state = StateInfo::new_from_position(&base);
+                let piece_values = PieceValues::new();
+                let state = StateInfo::new_from_position(&base, &piece_values);
                 let eval_list = EvalList::new(&base);
                 let eval_index_to_eval_list_index = EvalIndexToEvalListIndex::new(&eval_list);
                 let mut pos = Position {
@@ -1352,6 +1649,7 @@ impl Position {
                     eval_index_to_eval_list_index,
                     states: Vec::new(),
                     nodes: Arc::new(AtomicI64::new(0)),
+                    piece_values,
                 };
                 pos.init_states_and_push(state);
                 debug_assert!(pos.is_ok());
@@ -1367,10 +1665,28 @@ impl Position {
             eval_index_to_eval_list_index: pos.eval_index_to_eval_list_index.clone(),
             states: pos.states.clone(),
             nodes,
+            piece_values: pos.piece_values,
         };
         p.reserve_states();
         p
     }
+    // An independent copy of the current position without the move history.
+    #[allow(dead_code)]
+    pub fn snapshot(&self) -> Position {
+        let base = self.base.clone();
+        let state = StateInfo::new_from_position(&base, &self.piece_values);
+        let mut pos = Position {
+            base,
+            eval_list: self.eval_list.clone(),
+            eval_index_to_eval_list_index: self.eval_index_to_eval_list_index.clone(),
+            states: Vec::new(),
+            nodes: Arc::new(AtomicI64::new(0)),
+            piece_values: self.piece_values,
+        };
+        pos.init_states_and_push(state);
+        debug_assert!(pos.is_ok());
+        pos
+    }
     #[inline]
     pub fn pieces_c(&self, c: Color) -> Bitboard {
         self.base.pieces_c(c)
@@ -1383,6 +1699,11 @@ impl Position {
     pub fn pieces_cp(&self, c: Color, pt: PieceType) -> Bitboard {
         self.base.pieces_cp(c, pt)
     }
+    // Whether c has a piece other than pawns and the king on the board. Pieces in hand aren't counted.
+    pub fn has_non_pawn_material(&self, c: Color) -> bool {
+        self.pieces_c(c)
+            .and_to_bool(!self.pieces_pp(PieceType::PAWN, PieceType::KING))
+    }
     #[inline]
     pub fn pieces_pp(&self, pt0: PieceType, pt1: PieceType) -> Bitboard {
         self.base.pieces_pp(pt0, pt1)
@@ -1457,6 +1778,11 @@ impl Position {
     pub fn empty_bb(&self) -> Bitboard {
         self.base.empty_bb()
     }
+    // Every occupied square and the piece on it.
+    #[allow(dead_code)]
+    pub fn pieces(&self) -> impl Iterator<Item = (Square, Piece)> + '_ {
+        self.occupied_bb().map(move |sq| (sq, self.piece_on(sq)))
+    }
     #[inline]
     pub fn hand(&self, c: Color) -> Hand {
         self.base.hand(c)
@@ -1502,6 +1828,71 @@ impl Position {
     pub fn attackers_to_both_color(&self, to: Square, occupied: &Bitboard) -> Bitboard {
         self.base.attackers_to_both_color(to, occupied)
     }
+    // All squares attacked by c's pieces.
+    #[allow(dead_code)]
+    pub fn attacked_by(&self, c: Color) -> Bitboard {
+        let occupied = self.occupied_bb();
+        let mut bb = Bitboard::ZERO;
+        for sq in self.pieces_c(c) {
+            let pt = PieceType::new(self.piece_on(sq));
+            bb |= ATTACK_TABLE.attack(pt, c, sq, &occupied);
+        }
+        bb
+    }
+    // The number of pseudo-legal destinations of c's pieces except the king.
+    // Promotions and drops aren't counted, so this isn't the number of legal moves.
+    #[allow(dead_code)]
+    pub fn mobility(&self, c: Color) -> u32 {
+        let occupied = self.occupied_bb();
+        let target = !self.pieces_c(c);
+        let mut mobility = 0;
+        for sq in self.pieces_c(c) & !self.pieces_p(PieceType::KING) {
+            let pt = PieceType::new(self.piece_on(sq));
+            mobility += (ATTACK_TABLE.attack(pt, c, sq, &occupied) & target).count_ones();
+        }
+        mobility
+    }
+    // The number of the opponent pieces which attack the squares around the king.
+    // A piece attacking some of the squares is counted only once.
+    #[allow(dead_code)]
+    pub fn king_zone_attackers(&self, king_color: Color) -> u32 {
+        let them = king_color.inverse();
+        let occupied = self.occupied_bb();
+        let mut attackers = Bitboard::ZERO;
+        for sq in ATTACK_TABLE.king.attack(self.king_square(king_color)) {
+            attackers |= self.attackers_to(them, sq, &occupied);
+        }
+        attackers.count_ones()
+    }
+    #[allow(dead_code)]
+    pub fn diff(&self, other: &Position) -> PositionDiff {
+        let mut diff = PositionDiff::default();
+        for &sq in Square::ALL.iter() {
+            let before = self.piece_on(sq);
+            let after = other.piece_on(sq);
+            if before != after {
+                diff.squares.push((sq, before, after));
+            }
+        }
+        for &c in Color::ALL.iter() {
+            for &pt in PieceType::ALL_HAND.iter() {
+                let delta = other.hand(c).num(pt) as i32 - self.hand(c).num(pt) as i32;
+                if delta != 0 {
+                    diff.hands.push((c, pt, delta));
+                }
+            }
+        }
+        diff
+    }
+    // The number of pieces of the color and the piece type on the board. Hand pieces are not counted.
+    #[allow(dead_code)]
+    pub fn count(&self, c: Color, pt: PieceType) -> u32 {
+        self.pieces_cp(c, pt).count_ones()
+    }
+    #[allow(dead_code)]
+    pub fn count_all(&self, pt: PieceType) -> u32 {
+        self.pieces_p(pt).count_ones()
+    }
     #[allow(dead_code)]
     pub fn init_states(&mut self) {
         self.states.truncate(0);
@@ -1539,6 +1930,18 @@ impl Position {
     pub fn pinners_for_king(&self, color_of_king: Color) -> Bitboard {
         self.st().check_info.pinners_for_king(color_of_king)
     }
+    // (pinned square, pinner square)
+    #[allow(dead_code)]
+    pub fn pins(&self, king_color: Color) -> Vec<(Square, Square)> {
+        let ksq = self.king_square(king_color);
+        self.pinners_for_king(king_color)
+            .map(|pinner| {
+                let pinned = Bitboard::between_mask(ksq, pinner) & self.occupied_bb();
+                debug_assert_eq!(pinned.count_ones(), 1);
+                (pinned.lsb_unchecked(), pinner)
+            })
+            .collect()
+    }
     pub fn pseudo_legal<T: IsSearchingTrait>(&self, m: Move) -> bool {
         let us = self.side_to_move();
         let to;
@@ -1555,6 +1958,9 @@ impl Position {
             if self.piece_on(to) != Piece::EMPTY {
                 return false;
             }
+            if Position::is_dead_square(pt_dropped, us, to) {
+                return false;
+            }
             let checkers = self.checkers();
             match checkers.count_ones() {
                 0 => {}
@@ -1581,7 +1987,8 @@ impl Position {
                     Square::DELTA_S
                 };
                 let them = us.inverse();
-                if to.add_unchecked(delta) == self.king_square(them)
+                if !self.allow_drop_pawn_mate()
+                    && to.add_unchecked(delta) == self.king_square(them)
                     && self.is_drop_pawn_mate(us, to)
                 {
                     // drop pawn mate
@@ -1704,6 +2111,156 @@ impl Position {
         }
         true
     }
+    // Slow move generation which tries every move with pseudo_legal(). An oracle for MoveList.
+    #[cfg(test)]
+    pub fn brute_force_pseudo_legal(&self) -> Vec<Move> {
+        let us = self.side_to_move();
+        let mut moves = vec![];
+        for &to in Square::ALL.iter() {
+            for from in self.pieces_c(us) {
+                let pc = self.piece_on(from);
+                if self.pseudo_legal::<SearchingType>(Move::new_unpromote(from, to, pc)) {
+                    moves.push(Move::new_unpromote(from, to, pc));
+                }
+                // pseudo_legal::<SearchingType>() assumes that promotions are in the opponent field.
+                if (Rank::new(from).is_opponent_field(us) || Rank::new(to).is_opponent_field(us))
+                    && self.pseudo_legal::<SearchingType>(Move::new_promote(from, to, pc))
+                {
+                    moves.push(Move::new_promote(from, to, pc));
+                }
+            }
+            for &pt in PieceType::ALL_HAND.iter() {
+                let m = Move::new_drop(Piece::new(us, pt), to);
+                if self.pseudo_legal::<SearchingType>(m) {
+                    moves.push(m);
+                }
+            }
+        }
+        moves
+    }
+    // The squares where the unpromoted piece can't move any more.
+    fn is_dead_square(pt: PieceType, us: Color, to: Square) -> bool {
+        match pt {
+            PieceType::PAWN | PieceType::LANCE => {
+                Rank::new(to).is_in_front_of(us, RankAsBlack::RANK2)
+            }
+            PieceType::KNIGHT => Rank::new(to).is_in_front_of(us, RankAsBlack::RANK3),
+            _ => false,
+        }
+    }
+    // Same as pseudo_legal::<NotSearchingType>() but tells why the move is rejected.
+    #[allow(dead_code)]
+    pub fn pseudo_legal_reason(&self, m: Move) -> Result<(), IllegalReason> {
+        let us = self.side_to_move();
+        let to = m.to();
+        let checkers = self.checkers();
+        if m.is_drop() {
+            let pc_dropped = m.piece_dropped();
+            if Color::new(pc_dropped) != us {
+                return Err(IllegalReason::WrongSideToMove);
+            }
+            let pt_dropped = PieceType::new(pc_dropped);
+            if !self.hand(us).exist(pt_dropped) {
+                return Err(IllegalReason::DropNoPieceInHand);
+            }
+            if self.piece_on(to) != Piece::EMPTY {
+                return Err(IllegalReason::DropOnOccupied);
+            }
+            if Position::is_dead_square(pt_dropped, us, to) {
+                return Err(IllegalReason::DropOnDeadSquare);
+            }
+            match checkers.count_ones() {
+                0 => {}
+                1 => {
+                    let check_sq = checkers.lsb_unchecked();
+                    let droppables = Bitboard::between_mask(check_sq, self.king_square(us));
+                    if !droppables.is_set(to) {
+                        return Err(IllegalReason::NotEvasion);
+                    }
+                }
+                2 => return Err(IllegalReason::NotEvasion),
+                _ => unreachable!(),
+            }
+            if pt_dropped == PieceType::PAWN {
+                if (self.pieces_cp(us, PieceType::PAWN) & Bitboard::file_mask(File::new(to)))
+                    .to_bool()
+                {
+                    return Err(IllegalReason::Nifu);
+                }
+                let delta = if us == Color::BLACK {
+                    Square::DELTA_N
+                } else {
+                    Square::DELTA_S
+                };
+                if !self.allow_drop_pawn_mate()
+                    && to.add_unchecked(delta) == self.king_square(us.inverse())
+                    && self.is_drop_pawn_mate(us, to)
+                {
+                    return Err(IllegalReason::DropPawnMate);
+                }
+            }
+            return Ok(());
+        }
+        let from = m.from();
+        let pc_from = self.piece_on(from);
+        if pc_from == Piece::EMPTY || pc_from != m.piece_moved_before_move() {
+            return Err(IllegalReason::NoPieceOnFrom);
+        }
+        if Color::new(pc_from) != us {
+            return Err(IllegalReason::WrongSideToMove);
+        }
+        if self.pieces_c(us).is_set(to) {
+            return Err(IllegalReason::TargetOccupiedBySelf);
+        }
+        let pt_from = PieceType::new(pc_from);
+        if !ATTACK_TABLE
+            .attack(pt_from, us, from, &self.occupied_bb())
+            .is_set(to)
+        {
+            return Err(IllegalReason::NotAttackSquare);
+        }
+        if m.is_promotion() {
+            if !pc_from.is_promotable()
+                || (!Rank::new(from).is_opponent_field(us) && !Rank::new(to).is_opponent_field(us))
+            {
+                return Err(IllegalReason::IllegalPromotion);
+            }
+        } else {
+            // unpromote moves to the squares where the piece can't move any more.
+            if Position::is_dead_square(pt_from, us, to) {
+                return Err(IllegalReason::IllegalPromotion);
+            }
+        }
+        if checkers.to_bool() {
+            if pt_from == PieceType::KING {
+                if self
+                    .attackers_to(
+                        us.inverse(),
+                        to,
+                        &(self.occupied_bb() ^ Bitboard::square_mask(from)),
+                    )
+                    .to_bool()
+                {
+                    return Err(IllegalReason::NotEvasion);
+                }
+            } else {
+                match checkers.count_ones() {
+                    0 => {}
+                    1 => {
+                        let checker_sq = checkers.lsb_unchecked();
+                        let movables =
+                            Bitboard::between_mask(checker_sq, self.king_square(us)) | checkers;
+                        if !movables.is_set(to) {
+                            return Err(IllegalReason::NotEvasion);
+                        }
+                    }
+                    2 => return Err(IllegalReason::NotEvasion),
+                    _ => unreachable!(),
+                }
+            }
+        }
+        Ok(())
+    }
     pub fn legal(&self, m: Move) -> bool {
         // Repetition king check is illegal, but this function return legal.
         // Repetition king check is judged illegal(mated) in search functions.
@@ -1725,6 +2282,31 @@ impl Position {
         !self.blockers_for_king(us).is_set(from)
             || is_aligned_and_sq2_is_not_between_sq0_and_sq1(from, m.to(), self.king_square(us))
     }
+    // Play m on a copy of the board and check whether the king of the side to move is attacked.
+    // This is slow, but doesn't depend on pins. It is an oracle for legal().
+    #[allow(dead_code)]
+    pub fn leaves_own_king_in_check(&self, m: Move) -> bool {
+        let us = self.side_to_move();
+        let to = m.to();
+        let mut base = self.base.clone();
+        if m.is_drop() {
+            base.put_piece(m.piece_dropped(), to);
+        } else {
+            let from = m.from();
+            let pc = base.piece_on(from);
+            let captured = base.piece_on(to);
+            if captured != Piece::EMPTY {
+                base.remove_piece(captured, to);
+            }
+            base.remove_piece(pc, from);
+            base.put_piece(m.piece_moved_after_move(), to);
+            if PieceType::new(pc) == PieceType::KING {
+                base.king_squares[us.0 as usize] = to;
+            }
+        }
+        base.attackers_to(us.inverse(), base.king_square(us), &base.occupied_bb())
+            .to_bool()
+    }
     fn min_attacker(
         &self,
         to: Square,
@@ -1796,19 +2378,54 @@ impl Position {
         *attackers &= *occupied;
         PieceType::new(self.piece_on(sq))
     }
+    // The legal captures on sq by the side to move, including both promotion and non-promotion.
+    #[allow(dead_code)]
+    pub fn capturers_of(&self, sq: Square) -> Vec<Move> {
+        let pc = self.piece_on(sq);
+        if pc == Piece::EMPTY || Color::new(pc) == self.side_to_move() {
+            return vec![];
+        }
+        let mut mlist = MoveList::new();
+        mlist.generate_recaptures(self, sq);
+        let mut moves = vec![];
+        for ext_move in mlist.slice(0) {
+            let m = ext_move.mv;
+            if !self.legal(m) {
+                continue;
+            }
+            moves.push(m);
+            // generate_recaptures() only generates the promotion if it is possible.
+            if m.is_promotion() {
+                let m = Move::new_unpromote(m.from(), sq, self.piece_on(m.from()));
+                if self.pseudo_legal::<NotSearchingType>(m) {
+                    moves.push(m);
+                }
+            }
+        }
+        moves
+    }
+    // The capture value of the piece on sq. Zero for an empty square and a king.
+    pub fn value_on(&self, sq: Square) -> Value {
+        self.piece_values.capture_piece_value(self.piece_on(sq))
+    }
     pub fn see_ge(&self, m: Move, threshold: Value) -> bool {
         let to = m.to();
-        let mut balance = capture_piece_value(self.piece_on(to)) - threshold;
-        if balance < Value::ZERO {
-            return false;
-        }
         let is_drop = m.is_drop();
         let mut next_victim = if is_drop {
             m.piece_type_dropped()
         } else {
             PieceType::new(self.piece_on(m.from()))
         };
-        balance -= capture_piece_type_value(next_victim);
+        let mut balance = self.value_on(to) - threshold;
+        // the promoted piece is the one which can be captured next.
+        if m.is_promotion() {
+            balance += self.piece_values.promote_piece_type_value(next_victim);
+            next_victim = next_victim.to_promote();
+        }
+        if balance < Value::ZERO {
+            return false;
+        }
+        balance -= self.piece_values.capture_piece_type_value(next_victim);
         // in case next_victim == PieceType::KING return here.
         // ( capture_piece_type_value(PieceType::KING) == Value::ZERO )
         // it is ok if this move is legal.
@@ -1840,7 +2457,7 @@ impl Position {
                 self.min_attacker(to, &side_to_move_attackers, &mut occupied, &mut attackers);
             side_to_move = side_to_move.inverse();
             debug_assert!(balance < Value::ZERO);
-            balance = -balance - Value(1) - capture_piece_type_value(next_victim);
+            balance = -balance - Value(1) - self.piece_values.capture_piece_type_value(next_victim);
             if balance >= Value::ZERO {
                 if next_victim == PieceType::KING
                     && (attackers & self.pieces_c(side_to_move)).to_bool()
@@ -1853,23 +2470,128 @@ impl Position {
         }
         us != side_to_move
     }
-    pub fn is_drop_pawn_mate(&self, color_of_pawn: Color, sq_of_pawn: Square) -> bool {
-        debug_assert_eq!(
-            ATTACK_TABLE
-                .pawn
-                .attack(color_of_pawn, sq_of_pawn)
-                .count_ones(),
-            1
-        );
-        debug_assert_eq!(
-            ATTACK_TABLE
-                .pawn
-                .attack(color_of_pawn, sq_of_pawn)
-                .lsb_unchecked(),
-            self.king_square(color_of_pawn.inverse())
-        );
-
-        if !self
+    // The exchange value of m, found by bisecting the threshold of see_ge().
+    #[allow(dead_code)]
+    pub fn see(&self, m: Move) -> Value {
+        let mut lo = -Value::INFINITE; // see_ge(m, lo) is always true.
+        let mut hi = Value::INFINITE; // see_ge(m, hi) is always false.
+        while hi.0 - lo.0 > 1 {
+            let mid = Value((lo.0 + hi.0) / 2);
+            if self.see_ge(m, mid) {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
+    // SEE of the capture on "to" by the least valuable legal attacker of the side to move.
+    #[allow(dead_code)]
+    pub fn best_capture_see(&self, to: Square) -> Option<Value> {
+        let us = self.side_to_move();
+        let pc_captured = self.piece_on(to);
+        if pc_captured == Piece::EMPTY || Color::new(pc_captured) == us {
+            return None;
+        }
+        let mut candidates = self.attackers_to(us, to, &self.occupied_bb());
+        while candidates.to_bool() {
+            let mut occupied = self.occupied_bb();
+            let mut attackers = candidates;
+            let pt = self.min_attacker(to, &candidates, &mut occupied, &mut attackers);
+            let from = (candidates & self.pieces_p(pt)).lsb_unchecked();
+            let pc = self.piece_on(from);
+            let m = if pt.is_promotable()
+                && (Rank::new(to).is_opponent_field(us) || Rank::new(from).is_opponent_field(us))
+            {
+                Move::new_promote(from, to, pc)
+            } else {
+                Move::new_unpromote(from, to, pc)
+            };
+            if self.legal(m) {
+                return Some(self.see(m));
+            }
+            candidates ^= Bitboard::square_mask(from);
+        }
+        None
+    }
+    // The squares where pt in hand can be dropped, filtered as MoveList::generate_drop() does.
+    #[allow(dead_code)]
+    pub fn legal_drop_targets(&self, pt: PieceType) -> Bitboard {
+        let us = self.side_to_move();
+        if !self.hand(us).exist(pt) {
+            return Bitboard::ZERO;
+        }
+        let checkers = self.checkers();
+        let mut to_bb = match checkers.count_ones() {
+            0 => self.empty_bb(),
+            1 => Bitboard::between_mask(checkers.lsb_unchecked(), self.king_square(us)),
+            _ => return Bitboard::ZERO,
+        };
+        let mask1 = Bitboard::rank_mask(Rank::new_from_color_and_rank_as_black(
+            us,
+            RankAsBlack::RANK1,
+        ));
+        let mask2 = Bitboard::rank_mask(Rank::new_from_color_and_rank_as_black(
+            us,
+            RankAsBlack::RANK2,
+        ));
+        match pt {
+            PieceType::PAWN => {
+                to_bb &= !mask1;
+                // avoid two pawns.
+                for pawn_sq in self.pieces_cp(us, PieceType::PAWN) {
+                    to_bb &= !Bitboard::file_mask(File::new(pawn_sq));
+                }
+                // avoid drop pawn mate.
+                let drop_pawn_check_bb = ATTACK_TABLE
+                    .pawn
+                    .attack(us.inverse(), self.king_square(us.inverse()));
+                if !self.allow_drop_pawn_mate() && (drop_pawn_check_bb & to_bb).to_bool() {
+                    let to = drop_pawn_check_bb.lsb_unchecked();
+                    if self.is_drop_pawn_mate(us, to) {
+                        to_bb ^= Bitboard::square_mask(to);
+                    }
+                }
+            }
+            PieceType::LANCE => to_bb &= !mask1,
+            PieceType::KNIGHT => to_bb &= !(mask1 | mask2),
+            _ => {}
+        }
+        to_bb
+    }
+    // Hand, empty square, evasion, last ranks, two pawns and drop pawn mate are checked.
+    #[allow(dead_code)]
+    pub fn is_legal_drop(&self, pt: PieceType, to: Square) -> bool {
+        PieceType::ALL_HAND.contains(&pt) && self.legal_drop_targets(pt).is_set(to)
+    }
+    // Unlike is_drop_pawn_mate(), this can be called with any square.
+    // It returns false if the pawn dropped on the square doesn't give check.
+    #[allow(dead_code)]
+    pub fn would_be_drop_pawn_mate(&self, us: Color, to: Square) -> bool {
+        self.piece_on(to) == Piece::EMPTY
+            && ATTACK_TABLE
+                .pawn
+                .attack(us, to)
+                .is_set(self.king_square(us.inverse()))
+            && self.is_drop_pawn_mate(us, to)
+    }
+    pub fn is_drop_pawn_mate(&self, color_of_pawn: Color, sq_of_pawn: Square) -> bool {
+        debug_assert_eq!(
+            ATTACK_TABLE
+                .pawn
+                .attack(color_of_pawn, sq_of_pawn)
+                .count_ones(),
+            1
+        );
+        debug_assert_eq!(
+            ATTACK_TABLE
+                .pawn
+                .attack(color_of_pawn, sq_of_pawn)
+                .lsb_unchecked(),
+            self.king_square(color_of_pawn.inverse())
+        );
+
+        if !self
             .attackers_to(color_of_pawn, sq_of_pawn, &self.occupied_bb())
             .to_bool()
         {
@@ -1949,6 +2671,25 @@ impl Position {
         }
         Repetition::Not
     }
+    // is_repetition() with the favored color instead of the side to move relative result.
+    //   Repetition::Draw     => (side to move, Draw). Nobody is favored, so the color has no meaning.
+    //   Repetition::Win      => (side to move, PerpetualCheck). The opponent has been checking.
+    //   Repetition::Lose     => (opponent, PerpetualCheck). The side to move has been checking.
+    //   Repetition::Superior => (side to move, SuperiorHand).
+    //   Repetition::Inferior => (opponent, SuperiorHand).
+    #[allow(dead_code)]
+    pub fn repetition_outcome(&self) -> Option<(Color, RepetitionKind)> {
+        let us = self.side_to_move();
+        let them = us.inverse();
+        match self.is_repetition() {
+            Repetition::Not => None,
+            Repetition::Draw => Some((us, RepetitionKind::Draw)),
+            Repetition::Win => Some((us, RepetitionKind::PerpetualCheck)),
+            Repetition::Lose => Some((them, RepetitionKind::PerpetualCheck)),
+            Repetition::Superior => Some((us, RepetitionKind::SuperiorHand)),
+            Repetition::Inferior => Some((them, RepetitionKind::SuperiorHand)),
+        }
+    }
     pub fn is_entering_king_win(&self) -> bool {
         // CSA rule.
 
@@ -1977,33 +2718,120 @@ impl Position {
         //     先手の場合28点以上の持点がある。
         //     後手の場合27点以上の持点がある。
         //     点数の対象となるのは、宣言側の持駒と敵陣三段目以内に存在する玉を除く宣言側の駒のみである。
-        let own_big_pieces_count = (self.pieces_cpppp(
-            us,
+        if self.declaration_points(us) < Position::declaration_threshold(us) {
+            return false;
+        }
+        true
+    }
+    // Big pieces are 5 points and small pieces are 1 point.
+    // Pieces in hand and pieces on the opponent field except king are counted.
+    pub fn declaration_points(&self, c: Color) -> u32 {
+        let field = Bitboard::opponent_field_mask(c) & !self.pieces_cp(c, PieceType::KING);
+        let pieces_count = (self.pieces_c(c) & field).count_ones();
+        let big_pieces_count = (self.pieces_cpppp(
+            c,
             PieceType::BISHOP,
             PieceType::ROOK,
             PieceType::HORSE,
             PieceType::DRAGON,
-        ) & Bitboard::opponent_field_mask(us))
-        .count_ones();
-        let own_small_pieces_count = own_pieces_count - own_big_pieces_count;
-        let hand = self.hand(us);
-        let val = own_small_pieces_count
+        ) & field)
+            .count_ones();
+        let small_pieces_count = pieces_count - big_pieces_count;
+        let hand = self.hand(c);
+        small_pieces_count
             + hand.num(PieceType::PAWN)
             + hand.num(PieceType::LANCE)
             + hand.num(PieceType::KNIGHT)
             + hand.num(PieceType::SILVER)
             + hand.num(PieceType::GOLD)
-            + (own_big_pieces_count + hand.num(PieceType::BISHOP) + hand.num(PieceType::ROOK)) * 5;
-        let thresh = if us == Color::BLACK { 28 } else { 27 };
-        if val < thresh {
-            return false;
+            + (big_pieces_count + hand.num(PieceType::BISHOP) + hand.num(PieceType::ROOK)) * 5
+    }
+    fn declaration_threshold(c: Color) -> u32 {
+        if c == Color::BLACK {
+            28
+        } else {
+            27
         }
-        true
+    }
+    // Unlike is_repetition(), sennichite needs the same position 4 times in the whole game.
+    fn sennichite_result(&self) -> Option<GameResult> {
+        const SENNICHITE_COUNT: i32 = 4;
+        let mut count = 1;
+        let mut i = 4;
+        while i <= self.st().plies_from_null {
+            if self.states[self.states.len() - 1 - i as usize].key() == self.key() {
+                count += 1;
+                if count == SENNICHITE_COUNT {
+                    let us = self.side_to_move();
+                    return Some(if i <= self.st().continuous_check(us) {
+                        GameResult::win(us.inverse())
+                    } else if i <= self.st().continuous_check(us.inverse()) {
+                        GameResult::win(us)
+                    } else {
+                        GameResult::Draw
+                    });
+                }
+            }
+            i += 2;
+        }
+        None
+    }
+    // The result of the game if the current position ends it.
+    #[allow(dead_code)]
+    pub fn game_result(&self) -> Option<GameResult> {
+        let us = self.side_to_move();
+        if self.is_entering_king_win() {
+            return Some(GameResult::win(us));
+        }
+        if let Some(result) = self.sennichite_result() {
+            return Some(result);
+        }
+        let mut mlist = MoveList::new();
+        mlist.generate::<LegalType>(self, 0);
+        if mlist.size == 0 {
+            return Some(GameResult::win(us.inverse()));
+        }
+        None
+    }
+    // Both kings are on the opponent fields, and neither side has enough points to declare the win.
+    #[allow(dead_code)]
+    pub fn is_impasse_draw(&self) -> bool {
+        Color::ALL.iter().all(|&c| {
+            Rank::new(self.king_square(c)).is_opponent_field(c)
+                && self.declaration_points(c) < Position::declaration_threshold(c)
+        })
+    }
+    // The game is a draw when the game ply reaches max_ply.
+    #[allow(dead_code)]
+    pub fn is_max_moves_draw(&self, max_ply: i32) -> bool {
+        self.ply() >= max_ply
+    }
+    // Try rule. The king of the side to move has reached the starting square of the opponent king,
+    // and the opponent couldn't capture it.
+    #[allow(dead_code)]
+    pub fn is_try_win(&self) -> bool {
+        let us = self.side_to_move();
+        let target = if us == Color::BLACK {
+            Square::SQ51
+        } else {
+            Square::SQ59
+        };
+        self.king_square(us) == target && !self.in_check()
     }
     #[inline]
     pub fn key(&self) -> Key {
         self.st().key()
     }
+    // Calculates the key without the incremental updates of do_move().
+    #[allow(dead_code)]
+    pub fn key_from_scratch(&self) -> Key {
+        StateInfo::new_board_key(&self.base) ^ StateInfo::new_hand_key(&self.base)
+    }
+    // The key of the position after do_null_move(). Only the side to move changes.
+    #[allow(dead_code)]
+    pub fn key_after_null(&self) -> Key {
+        self.key() ^ Zobrist::COLOR
+    }
     #[inline]
     fn board_key(&self) -> Key {
         self.st().board_key
@@ -2016,6 +2844,48 @@ impl Position {
     pub fn material(&self) -> Value {
         self.st().material
     }
+    // The material on the board and in hands. Their sum is material().
+    #[allow(dead_code)]
+    pub fn board_material(&self) -> Value {
+        StateInfo::new_board_material(&self.base, &self.piece_values)
+    }
+    #[allow(dead_code)]
+    pub fn hand_material(&self) -> Value {
+        StateInfo::new_hand_material(&self.base, &self.piece_values)
+    }
+    #[allow(dead_code)]
+    pub fn piece_values(&self) -> &PieceValues {
+        &self.piece_values
+    }
+    // Recalculates the material of all the states, walking back the moves from the current one.
+    #[allow(dead_code)]
+    pub fn set_piece_values(&mut self, piece_values: PieceValues) {
+        self.piece_values = piece_values;
+        let mut material = StateInfo::new_material(&self.base, &self.piece_values);
+        let mut us = self.side_to_move();
+        for st in self.states.iter_mut().rev() {
+            st.material = material;
+            // "us" is the side which made the move leading to "st". A null move also changes it.
+            us = us.inverse();
+            if let Some(m) = st.last_move {
+                let mut gain = self.piece_values.capture_piece_value(st.captured_piece);
+                if m.is_promotion() {
+                    gain += self
+                        .piece_values
+                        .promote_piece_type_value(PieceType::new(m.piece_moved_before_move()));
+                }
+                material -= if us == Color::BLACK { gain } else { -gain };
+            }
+        }
+    }
+    #[inline]
+    pub fn allow_drop_pawn_mate(&self) -> bool {
+        self.base.allow_drop_pawn_mate
+    }
+    #[allow(dead_code)]
+    pub fn set_allow_drop_pawn_mate(&mut self, allow: bool) {
+        self.base.allow_drop_pawn_mate = allow;
+    }
     pub fn material_diff(&self) -> Value {
         self.st().material - self.states[self.states.len() - 2].material
     }
@@ -2025,26 +2895,109 @@ impl Position {
     #[allow(dead_code)]
     #[inline]
     pub fn print(&self) {
-        self.base.print();
-        println!("key: {}", self.key().0);
+        println!("{}", self);
     }
     #[inline]
     pub fn to_sfen(&self) -> String {
         self.base.to_sfen()
     }
+    // SFEN without the ply field, so the same position reached at a different move number gives the same string.
+    #[allow(dead_code)]
+    pub fn position_signature(&self) -> String {
+        self.base.position_signature()
+    }
+    #[allow(dead_code)]
+    pub fn board_sfen(&self) -> String {
+        self.base.board_sfen()
+    }
+    #[allow(dead_code)]
+    pub fn hands_sfen(&self) -> String {
+        self.base.hands_sfen()
+    }
     #[allow(dead_code)]
     #[inline]
     pub fn to_csa_string(&self) -> String {
         self.base.to_csa_string()
     }
+    // Board rotated 180 degrees, and colors of pieces, hands and side to move swapped.
+    #[allow(dead_code)]
+    pub fn flip(&self) -> Position {
+        let swap_case = |c: char| {
+            if c.is_ascii_uppercase() {
+                c.to_ascii_lowercase()
+            } else {
+                c.to_ascii_uppercase()
+            }
+        };
+        let sfen = self.to_sfen();
+        let args = sfen.split_whitespace().collect::<Vec<&str>>();
+        let board = args[0]
+            .split('/')
+            .rev()
+            .map(|row| {
+                let mut tokens = vec![];
+                let mut promoted = false;
+                for c in row.chars() {
+                    if c == '+' {
+                        promoted = true;
+                        continue;
+                    }
+                    let mut token = if promoted {
+                        "+".to_string()
+                    } else {
+                        "".to_string()
+                    };
+                    token.push(swap_case(c));
+                    tokens.push(token);
+                    promoted = false;
+                }
+                tokens.reverse();
+                tokens.concat()
+            })
+            .collect::<Vec<String>>()
+            .join("/");
+        let side_to_move = if args[1] == "b" { "w" } else { "b" };
+        let hands = args[2].chars().map(swap_case).collect::<String>();
+        let flipped = format!("{} {} {} {}", board, side_to_move, hands, args[3]);
+        Position::new_from_sfen(&flipped).unwrap()
+    }
     #[inline]
     pub fn checkers(&self) -> Bitboard {
         self.st().checkers_bb
     }
+    // The squares where a move blocks or captures the checker. Bitboard::ZERO unless single check.
+    pub fn check_resolution_squares(&self) -> Bitboard {
+        let checkers = self.checkers();
+        if checkers.count_ones() != 1 {
+            return Bitboard::ZERO;
+        }
+        let checker_sq = checkers.lsb_unchecked();
+        Bitboard::between_mask(checker_sq, self.king_square(self.side_to_move())) | checkers
+    }
     #[inline]
     pub fn in_check(&self) -> bool {
         self.checkers().to_bool()
     }
+    // Whether there is exactly one legal evasion. Counting stops at the second legal one.
+    pub fn has_single_evasion(&self) -> bool {
+        debug_assert!(self.in_check());
+        let mut mlist = MoveList::new();
+        mlist.generate::<EvasionsType>(self, 0);
+        mlist
+            .slice(0)
+            .iter()
+            .filter(|x| self.legal(x.mv))
+            .take(2)
+            .count()
+            == 1
+    }
+    #[allow(dead_code)]
+    pub fn checker_count(&self) -> u32 {
+        self.checkers().count_ones()
+    }
+    pub fn is_double_check(&self) -> bool {
+        self.checker_count() == 2
+    }
     #[allow(dead_code)]
     pub fn nodes_searched(&self) -> i64 {
         (*self.nodes).load(Ordering::Relaxed)
@@ -2080,6 +3033,28 @@ impl Position {
         }
         false
     }
+    // A check which is neither a capture nor a pawn promotion.
+    #[allow(dead_code)]
+    pub fn is_quiet_check(&self, m: Move) -> bool {
+        self.gives_check(m) && !m.is_capture(self) && !m.is_pawn_promotion()
+    }
+    // Overwrite buf with the legal moves. A MoveList is large, so callers can reuse one buffer.
+    #[allow(dead_code)]
+    pub fn fill_legal(&self, buf: &mut MoveList) {
+        buf.generate::<LegalType>(self, 0);
+    }
+    // The move is undone when the returned guard is dropped.
+    #[allow(dead_code)]
+    pub fn do_move_scoped(&mut self, m: Move) -> MoveGuard<'_> {
+        self.do_move_auto(m);
+        MoveGuard { pos: self, m }
+    }
+    // do_move() which computes gives_check itself.
+    #[allow(dead_code)]
+    pub fn do_move_auto(&mut self, m: Move) {
+        let gives_check = self.gives_check(m);
+        self.do_move(m, gives_check);
+    }
     pub fn do_move(&mut self, m: Move, gives_check: bool) {
         debug_assert!(self.is_ok());
         (*self.nodes).fetch_add(1, Ordering::Relaxed);
@@ -2092,6 +3067,7 @@ impl Position {
         }
         self.base.game_ply += 1;
         self.st_mut().plies_from_null += 1;
+        self.st_mut().last_move = Some(m);
 
         let us = self.side_to_move();
         let them = us.inverse();
@@ -2116,9 +3092,6 @@ impl Position {
             board_key ^= Zobrist::get_field(pt_to, to, us);
             self.base.hands[us.0 as usize].minus_one(pt_to);
             self.base.put_piece(pc_to, to);
-
-            // set golds_bb before using attackers_to_except_king.
-            self.base.set_golds_bb();
             if gives_check {
                 // only one direct check.
                 self.st_mut().checkers_bb = Bitboard::square_mask(to);
@@ -2159,19 +3132,21 @@ impl Position {
 
                 board_key ^= Zobrist::get_field(pt_captured, to, them);
                 hand_key ^= Zobrist::get_hand(pt_captured_demoted, hand_num, us);
+                let captured_value = self.piece_values.capture_piece_type_value(pt_captured);
                 self.st_mut().material += if us == Color::BLACK {
-                    capture_piece_type_value(pt_captured)
+                    captured_value
                 } else {
-                    -capture_piece_type_value(pt_captured)
+                    -captured_value
                 };
             } else {
                 captured_piece = Piece::EMPTY;
             }
             let pc_to = if m.is_promotion() {
+                let promote_value = self.piece_values.promote_piece_type_value(pt_from);
                 self.st_mut().material += if us == Color::BLACK {
-                    promote_piece_type_value(pt_from)
+                    promote_value
                 } else {
-                    -promote_piece_type_value(pt_from)
+                    -promote_value
                 };
                 pc_from.to_promote()
             } else {
@@ -2202,9 +3177,6 @@ impl Position {
             board_key ^= Zobrist::get_field(pt_from, from, us);
             board_key ^= Zobrist::get_field(pt_to, to, us);
 
-            // set golds_bb before using attackers_to_except_king.
-            self.base.set_golds_bb();
-
             if gives_check {
                 self.st_mut().checkers_bb =
                     self.attackers_to_except_king(us, self.king_square(them), &self.occupied_bb())
@@ -2293,12 +3265,45 @@ impl Position {
                     .set(eval_list_index, Color::WHITE, new_eval_index.inverse());
             }
         }
-        self.base.set_golds_bb();
         self.base.side_to_move = them;
         self.base.game_ply -= 1;
         self.states.pop();
         debug_assert!(self.is_ok());
     }
+    #[allow(dead_code)]
+    pub fn do_moves(&mut self, moves: &[Move]) {
+        for &m in moves {
+            let gives_check = self.gives_check(m);
+            self.do_move(m, gives_check);
+        }
+    }
+    // Undo the last n moves. They must not include null moves.
+    #[allow(dead_code)]
+    pub fn undo_moves(&mut self, n: usize) {
+        for _ in 0..n {
+            let m = self
+                .st()
+                .last_move
+                .expect("Error: undo_moves() can't undo a null move or beyond the root position.");
+            self.undo_move(m);
+        }
+    }
+    // The number of the moves from the beginning of the line which are legal in sequence.
+    // The position is restored before returning.
+    #[allow(dead_code)]
+    pub fn validate_line(&mut self, moves: &[Move]) -> usize {
+        let mut n = 0;
+        for &m in moves {
+            if !self.pseudo_legal::<NotSearchingType>(m) || !self.legal(m) {
+                break;
+            }
+            let gives_check = self.gives_check(m);
+            self.do_move(m, gives_check);
+            n += 1;
+        }
+        self.undo_moves(n);
+        n
+    }
     pub fn do_null_move(&mut self) {
         debug_assert!(self.is_ok());
         {
@@ -2313,6 +3318,7 @@ impl Position {
         self.st_mut().hand_of_side_to_move = self.hand(them);
         self.st_mut().captured_piece = Piece::EMPTY;
         self.st_mut().check_info = CheckInfo::new(&self.base);
+        self.st_mut().last_move = None;
         debug_assert!(self.is_ok());
     }
     pub fn undo_null_move(&mut self) {
@@ -2436,7 +3442,6 @@ impl Position {
                     } else {
                         pos_base.exchange_pieces(pc, to);
                     }
-                    pos_base.set_golds_bb();
                     let (blockers, _pinners) =
                         pos_base.slider_blockers_and_pinners(&pos_base.pieces_c(us), us, ksq);
                     let king_escape_candidates =
@@ -2490,7 +3495,6 @@ impl Position {
                     } else {
                         pos_base.exchange_pieces(pc, to);
                     }
-                    pos_base.set_golds_bb();
                     let (blockers, _pinners) =
                         pos_base.slider_blockers_and_pinners(&pos_base.pieces_c(us), us, ksq);
                     let pt = PieceType::new(pc);
@@ -2508,7 +3512,6 @@ impl Position {
                         if attack.is_set(ksq) {
                             pos_base.remove_piece(pc, to);
                             pos_base.put_piece(pc.to_promote(), to);
-                            pos_base.set_golds_bb();
                             let mut king_escape_candidates =
                                 ATTACK_TABLE.king.attack(ksq) & !pos_base.pieces_c(them);
                             king_escape_candidates &= !self
@@ -2566,7 +3569,6 @@ impl Position {
                             }
                             pos_base.remove_piece(pc.to_promote(), to);
                             pos_base.put_piece(pc, to);
-                            pos_base.set_golds_bb();
                         }
                     }
                     // not promote
@@ -2779,7 +3781,7 @@ impl Position {
             panic!("position is ng. line: {}", line!());
         }
 
-        let tmp_state = StateInfo::new_from_position(&self.base);
+        let tmp_state = StateInfo::new_from_position(&self.base, &self.piece_values);
         if self.material() != tmp_state.material {
             panic!("position is ng. line: {}", line!());
         }
@@ -2826,6 +3828,52 @@ impl Position {
     }
 }
 
+// Play random legal moves from the start position. Stop early if the game ends.
+#[allow(dead_code)]
+pub fn random_legal_position(rng: &mut impl Rng, max_plies: u32) -> Position {
+    let mut pos = Position::new();
+    for _ in 0..max_plies {
+        if pos.game_result().is_some() {
+            break;
+        }
+        let mut mlist = MoveList::new();
+        mlist.generate::<LegalType>(&pos, 0);
+        let m = mlist.slice(0)[rng.gen_range(0, mlist.size)].mv;
+        let gives_check = pos.gives_check(m);
+        pos.do_move(m, gives_check);
+    }
+    pos
+}
+
+// Do and undo m, and check that the incrementally updated state is restored.
+#[allow(dead_code)]
+pub fn assert_do_undo_consistency(pos: &mut Position, m: Move) {
+    let key = pos.key();
+    let material = pos.material();
+    let eval_list = pos.eval_list().clone();
+    let gives_check = pos.gives_check(m);
+    pos.do_move(m, gives_check);
+    pos.undo_move(m);
+    assert_eq!(
+        pos.key().0,
+        key.0,
+        "key mismatch after {}",
+        m.to_usi_string()
+    );
+    assert_eq!(
+        pos.material(),
+        material,
+        "material mismatch after {}",
+        m.to_usi_string()
+    );
+    assert_eq!(
+        pos.eval_list(),
+        &eval_list,
+        "eval list mismatch after {}",
+        m.to_usi_string()
+    );
+}
+
 #[test]
 fn test_position_set() {
     let sfens = [
@@ -2890,6 +3938,39 @@ fn test_position_set() {
     }
 }
 
+#[test]
+fn test_position_promoted_piece_in_hand() {
+    let sfens = [
+        ("4k4/9/9/9/9/9/9/9/4K4 b +P 1", "+P"),
+        ("4k4/9/9/9/9/9/9/9/4K4 b R2+p 1", "+p"),
+        ("4k4/9/9/9/9/9/9/9/4K4 w G+Bb 1", "+B"),
+    ];
+    for &(sfen, chars) in sfens.iter() {
+        assert_eq!(
+            Position::new_from_sfen(sfen).err(),
+            Some(SfenError::PromotedPieceInHand {
+                chars: chars.to_string()
+            })
+        );
+    }
+    assert!(Position::new_from_sfen("4k4/9/9/9/9/9/9/9/4K4 b R2p 1").is_ok());
+}
+
+#[test]
+fn test_position_is_double_check() {
+    let pos = Position::new();
+    assert_eq!(pos.checker_count(), 0);
+    assert!(!pos.is_double_check());
+
+    let pos = Position::new_from_sfen("4k4/9/9/9/4r4/9/9/9/4K4 b - 1").unwrap();
+    assert_eq!(pos.checker_count(), 1);
+    assert!(!pos.is_double_check());
+
+    let pos = Position::new_from_sfen("4k4/9/9/9/b3r4/9/9/9/4K4 b - 1").unwrap();
+    assert_eq!(pos.checker_count(), 2);
+    assert!(pos.is_double_check());
+}
+
 #[test]
 fn test_position_attackers_to() {
     let sfens = ["lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1"];
@@ -3113,81 +4194,1223 @@ fn test_position_see_ge() {
 }
 
 #[test]
-fn test_position_gives_check() {
-    const CHECK: bool = true;
-    const NOT_CHECK: bool = false;
-    let array = [
-        (
-            "8k/9/9/9/9/9/9/9/K8 b Rr 1",
-            vec![("R*1b", CHECK), ("R*1h", CHECK), ("R*2b", NOT_CHECK)],
-        ),
-        (
-            "8k/9/9/9/9/9/9/9/K8 w Rr 1",
-            vec![("R*9h", CHECK), ("R*9b", CHECK), ("R*8h", NOT_CHECK)],
-        ),
-        (
-            "8k/9/9/9/9/9/9/8G/K7L b Rr 1",
-            vec![("1h2h", CHECK), ("1h1g", NOT_CHECK)],
-        ),
-    ];
-    for (sfen, move_candidates) in array.iter() {
-        let pos = Position::new_from_sfen(sfen).unwrap();
-        for &(move_str, is_check) in move_candidates {
-            let m = Move::new_from_usi_str(move_str, &pos);
-            assert!(m.is_some());
-            assert_eq!(pos.gives_check(m.unwrap()), is_check);
-        }
-    }
+fn test_position_see_ge_promotion() {
+    let sfen = "8k/9/4g4/4P4/9/9/9/9/4K4 b - 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    let m_promote = Move::new_from_usi_str("5d5c+", &pos).unwrap();
+    let m_unpromote = Move::new_from_usi_str("5d5c", &pos).unwrap();
+    let gain = capture_piece_type_value(PieceType::GOLD);
+    let promote_gain = promote_piece_type_value(PieceType::PAWN);
+    assert!(pos.see_ge(m_unpromote, gain));
+    assert!(!pos.see_ge(m_unpromote, gain + Value(1)));
+    assert!(pos.see_ge(m_promote, gain + promote_gain));
+    assert!(!pos.see_ge(m_promote, gain + promote_gain + Value(1)));
+
+    // recapturing takes the promoted pawn.
+    let sfen = "8k/4s4/4g4/4P4/9/9/9/9/4K4 b - 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    let m_promote = Move::new_from_usi_str("5d5c+", &pos).unwrap();
+    let m_unpromote = Move::new_from_usi_str("5d5c", &pos).unwrap();
+    let loss = capture_piece_type_value(PieceType::PRO_PAWN);
+    assert!(pos.see_ge(
+        m_unpromote,
+        gain - capture_piece_type_value(PieceType::PAWN)
+    ));
+    assert!(pos.see_ge(m_promote, gain + promote_gain - loss));
+    assert!(!pos.see_ge(m_promote, gain + promote_gain - loss + Value(1)));
 }
 
 #[test]
-fn test_position_do_move() {
-    let sfen_and_moves_array = [
-        ("4k4/9/9/9/9/9/9/9/4K4 b Bb 1", vec!["B*5g", "B*5c"]),
-        (
-            "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1",
-            vec![
-                "7g7f", "3c3d", "2g2f", "5c5d", "5g5f", "2b8h+", "7i8h", "B*5g", "B*5c", "8b5b",
-                "5c8f+", "5a6b", "3i4h", "5g2d+", "8h7g", "5d5e", "2f2e", "2d3e", "5f5e", "5b5e",
-                "P*5g", "7a7b", "7g6f", "5e5a", "3g3f", "3e4d", "2e2d", "2c2d", "2h2d", "3a3b",
-                "5i6h", "6b7a", "4g4f", "P*5f", "5g5f", "5a5f", "4i5h", "P*2c", "2d2g", "5f5h+",
-                "6i5h", "G*8h", "8i7g", "8h9i", "7g6e", "L*5a", "P*5e", "5a5e", "5h4g", "P*5f",
-                "P*5h", "9i9h", "2g2h", "4a5b", "R*3a",
-            ],
-        ),
-    ];
-    for (sfen, moves) in sfen_and_moves_array.iter() {
-        let mut pos = Position::new_from_sfen(sfen).unwrap();
-        for move_str in moves {
-            let m = Move::new_from_usi_str(move_str, &pos);
-            assert!(m.is_some());
-            let m = m.unwrap();
-            let gives_check = pos.gives_check(m);
-            {
-                // Checking Position::do_move and Position::undo_move work accurately.
-                // (Position::do_move and Position::undo_move call is_ok().)
-                pos.do_move(m, gives_check);
-                pos.undo_move(m);
-            }
-            pos.do_move(m, gives_check);
-            assert!(pos.is_repetition() == Repetition::Not);
-        }
-    }
+fn test_position_best_capture_see() {
+    let pawn = capture_piece_type_value(PieceType::PAWN);
+    let silver = capture_piece_type_value(PieceType::SILVER);
+    let rook = capture_piece_type_value(PieceType::ROOK);
+
+    // The pawn captures first. The gold doesn't recapture because the rook is behind the pawn.
+    let sfen = "4k4/9/4g4/4s4/4P4/9/6B2/4R4/4K4 b - 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    assert_eq!(pos.best_capture_see(Square::SQ54), Some(silver));
+    let m = Move::new_from_usi_str("5e5d", &pos).unwrap();
+    assert_eq!(pos.see(m), silver);
+    assert_eq!(pos.best_capture_see(Square::SQ55), None);
+    assert_eq!(pos.best_capture_see(Square::SQ56), None);
+
+    // The only attacker loses the rook for a pawn.
+    let sfen = "4k4/9/4g4/4p4/9/9/9/4R4/4K4 b - 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    assert_eq!(pos.best_capture_see(Square::SQ54), Some(pawn - rook));
+
+    // The pawn is pinned by the bishop, so the rook captures and the gold recaptures.
+    let sfen = "4k4/9/1b1g5/3s4R/3P5/9/5K3/9/9 b - 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    assert_eq!(pos.best_capture_see(Square::SQ64), Some(silver - rook));
 }
 
 #[test]
-fn test_check_info_new() {
-    // CheckInfo::check_squares in CheckInfo::new() depends on the following assumptions.
-    assert_eq!(0, PieceType::OCCUPIED.0);
-    assert_eq!(1, PieceType::PAWN.0);
-    assert_eq!(2, PieceType::LANCE.0);
-    assert_eq!(3, PieceType::KNIGHT.0);
-    assert_eq!(4, PieceType::SILVER.0);
-    assert_eq!(5, PieceType::BISHOP.0);
-    assert_eq!(6, PieceType::ROOK.0);
-    assert_eq!(7, PieceType::GOLD.0);
-    assert_eq!(8, PieceType::KING.0);
-    assert_eq!(9, PieceType::PRO_PAWN.0);
+fn test_position_see_ge_drop() {
+    let rook = capture_piece_type_value(PieceType::ROOK);
+    let pawn = capture_piece_type_value(PieceType::PAWN);
+
+    // a drop onto an undefended square.
+    let sfen = "4k4/9/9/4p4/9/9/9/9/4K4 b R 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    let m = Move::new_from_usi_str("R*1e", &pos).unwrap();
+    assert!(pos.see_ge(m, Value(0)));
+    assert!(!pos.see_ge(m, Value(1)));
+
+    // a rook drop onto a square defended by a pawn.
+    let m = Move::new_from_usi_str("R*5e", &pos).unwrap();
+    assert!(!pos.see_ge(m, Value(0)));
+    assert!(pos.see_ge(m, -rook));
+    assert!(!pos.see_ge(m, -rook + Value(1)));
+
+    // the drop initiates an exchange. The gold recaptures the pawn.
+    let sfen = "4k4/9/9/4p4/9/4G4/9/9/4K4 b R 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    let m = Move::new_from_usi_str("R*5e", &pos).unwrap();
+    assert!(!pos.see_ge(m, Value(0)));
+    assert!(pos.see_ge(m, -rook + pawn));
+    assert!(!pos.see_ge(m, -rook + pawn + Value(1)));
+
+    // a pawn drop that is traded evenly thanks to the lance.
+    let sfen = "4k4/9/9/p8/9/9/9/9/L3K4 b P 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    let m = Move::new_from_usi_str("P*9e", &pos).unwrap();
+    assert!(pos.see_ge(m, Value(0)));
+    assert!(!pos.see_ge(m, Value(1)));
+}
+
+#[test]
+fn test_position_board_material_and_hand_material() {
+    let sfen = "4k4/9/9/9/9/9/9/1B7/R3K4 b G2Pr 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    let value = |pt| pos.piece_values().piece_type_value(pt);
+    assert_eq!(
+        pos.board_material(),
+        value(PieceType::ROOK) + value(PieceType::BISHOP)
+    );
+    assert_eq!(
+        pos.hand_material(),
+        value(PieceType::GOLD) + value(PieceType::PAWN) * 2 - value(PieceType::ROOK)
+    );
+    assert_eq!(pos.board_material() + pos.hand_material(), pos.material());
+
+    let pos = Position::new();
+    assert_eq!(pos.board_material(), Value(0));
+    assert_eq!(pos.hand_material(), Value(0));
+}
+
+#[test]
+fn test_position_apply_usi_moves() {
+    let mut pos = Position::new();
+    assert_eq!(
+        pos.apply_usi_moves(&["7g7f", "3c3d", "7f7d", "8c8d"]),
+        Err((
+            2,
+            SfenError::IllegalMove {
+                move_str: "7f7d".to_string()
+            }
+        ))
+    );
+    assert_eq!(pos.ply(), 3);
+    assert_eq!(
+        pos.to_sfen(),
+        Position::from_usi_moves(&["7g7f", "3c3d"])
+            .unwrap()
+            .to_sfen()
+    );
+
+    let mut pos = Position::new();
+    assert_eq!(pos.apply_usi_moves(&["7g7f", "3c3d", "8h2b+"]), Ok(()));
+    assert_eq!(pos.ply(), 4);
+}
+
+#[test]
+fn test_position_do_move_auto() {
+    let moves = [
+        "7g7f", "3c3d", "8h2b+", "3a2b", "B*5e", "5a4b", "5e3c+", "4b3c",
+    ];
+    let mut pos = Position::new();
+    let mut pos_auto = Position::new();
+    for move_str in moves.iter() {
+        let m = Move::new_from_usi_str(move_str, &pos).unwrap();
+        let gives_check = pos.gives_check(m);
+        pos.do_move(m, gives_check);
+        pos_auto.do_move_auto(m);
+        assert_eq!(pos_auto.to_sfen(), pos.to_sfen());
+        assert_eq!(pos_auto.key().0, pos.key().0);
+        assert_eq!(pos_auto.checkers(), pos.checkers());
+    }
+}
+
+#[test]
+fn test_position_capturers_of() {
+    // The same position as test_generate_recaptures().
+    let sfen = "k1B1R1+B2/9/4p+R3/3SPG3/3N5/9/9/9/K8 b p 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    let mut captures = pos
+        .capturers_of(Square::SQ53)
+        .iter()
+        .map(|m| m.to_csa_string(&pos))
+        .collect::<Vec<_>>();
+    captures.sort();
+    assert_eq!(
+        captures,
+        [
+            "3153UM", "4353RY", "4453KI", "5153HI", "5153RY", "5453FU", "5453TO", "6453GI",
+            "6453NG", "6553KE", "6553NK", "7153KA", "7153UM"
+        ]
+    );
+    assert!(pos.capturers_of(Square::SQ54).is_empty());
+    assert!(pos.capturers_of(Square::SQ55).is_empty());
+}
+
+#[test]
+fn test_position_position_signature() {
+    let pos0 = Position::new_from_sfen(
+        "lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 2",
+    )
+    .unwrap();
+    let pos1 = Position::new_from_sfen(
+        "lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 40",
+    )
+    .unwrap();
+    assert_eq!(
+        pos0.position_signature(),
+        "lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w -"
+    );
+    assert_eq!(pos0.position_signature(), pos1.position_signature());
+    assert_ne!(pos0.to_sfen(), pos1.to_sfen());
+
+    let pos2 = Position::new_from_sfen(
+        "lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL b - 2",
+    )
+    .unwrap();
+    assert_ne!(pos0.position_signature(), pos2.position_signature());
+}
+
+#[test]
+fn test_position_do_move_scoped() {
+    let mut pos = Position::new();
+    let sfen = pos.to_sfen();
+    let key = pos.key().0;
+    {
+        let m = Move::new_from_usi_str("7g7f", &pos).unwrap();
+        let mut guard = pos.do_move_scoped(m);
+        assert_eq!(guard.ply(), 2);
+        // guards can be nested.
+        let m = Move::new_from_usi_str("3c3d", &guard).unwrap();
+        let guard2 = guard.do_move_scoped(m);
+        assert_eq!(guard2.ply(), 3);
+    }
+    assert_eq!(pos.to_sfen(), sfen);
+    assert_eq!(pos.key().0, key);
+
+    // early return in a closure.
+    let mut find = |pos: &mut Position, move_str: &str| -> bool {
+        let m = Move::new_from_usi_str(move_str, pos).unwrap();
+        let guard = pos.do_move_scoped(m);
+        if guard.in_check() {
+            return true;
+        }
+        false
+    };
+    assert!(!find(&mut pos, "2g2f"));
+    assert_eq!(pos.to_sfen(), sfen);
+}
+
+#[test]
+fn test_position_value_on() {
+    let pos = Position::new_from_sfen("4k4/9/9/9/4r4/9/9/9/4K4 b - 1").unwrap();
+    assert_eq!(
+        pos.value_on(Square::SQ55),
+        capture_piece_type_value(PieceType::ROOK)
+    );
+    assert_eq!(pos.value_on(Square::SQ56), Value::ZERO);
+    assert_eq!(pos.value_on(Square::SQ59), Value::ZERO);
+}
+
+#[test]
+fn test_position_pins() {
+    let sfen = "k8/9/9/9/4r4/9/9/4S4/4K4 b - 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    assert_eq!(pos.pins(Color::BLACK), vec![(Square::SQ58, Square::SQ55)]);
+    assert!(pos.pins(Color::WHITE).is_empty());
+
+    // the silver is not pinned if another piece is on the line.
+    let sfen = "k8/9/9/9/4r4/4P4/9/4S4/4K4 b - 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    assert!(pos.pins(Color::BLACK).is_empty());
+}
+
+#[test]
+fn test_position_pseudo_legal_reason() {
+    let sfen = "lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    let array = [
+        (
+            Move::new_unpromote(Square::SQ76, Square::SQ75, Piece::B_PAWN),
+            IllegalReason::WrongSideToMove,
+        ),
+        (
+            Move::new_drop(Piece::B_PAWN, Square::SQ55),
+            IllegalReason::WrongSideToMove,
+        ),
+        (
+            Move::new_unpromote(Square::SQ55, Square::SQ56, Piece::W_PAWN),
+            IllegalReason::NoPieceOnFrom,
+        ),
+        (
+            Move::new_unpromote(Square::SQ22, Square::SQ33, Piece::W_BISHOP),
+            IllegalReason::TargetOccupiedBySelf,
+        ),
+        (
+            Move::new_unpromote(Square::SQ22, Square::SQ77, Piece::W_BISHOP),
+            IllegalReason::NotAttackSquare,
+        ),
+        (
+            Move::new_drop(Piece::W_PAWN, Square::SQ55),
+            IllegalReason::DropNoPieceInHand,
+        ),
+        (
+            Move::new_promote(Square::SQ53, Square::SQ54, Piece::W_PAWN),
+            IllegalReason::IllegalPromotion,
+        ),
+    ];
+    for (m, reason) in array.iter() {
+        assert_eq!(pos.pseudo_legal_reason(*m), Err(*reason));
+        assert!(!pos.pseudo_legal::<NotSearchingType>(*m));
+    }
+
+    let sfen = "4k4/9/4P4/9/9/9/9/9/4K4 b Pp 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    assert_eq!(
+        pos.pseudo_legal_reason(Move::new_drop(Piece::B_PAWN, Square::SQ53)),
+        Err(IllegalReason::DropOnOccupied)
+    );
+    assert_eq!(
+        pos.pseudo_legal_reason(Move::new_drop(Piece::B_PAWN, Square::SQ57)),
+        Err(IllegalReason::Nifu)
+    );
+    assert_eq!(
+        pos.pseudo_legal_reason(Move::new_drop(Piece::B_PAWN, Square::SQ11)),
+        Err(IllegalReason::DropOnDeadSquare)
+    );
+    assert!(!pos.pseudo_legal::<SearchingType>(Move::new_drop(Piece::B_PAWN, Square::SQ11)));
+    assert_eq!(
+        pos.pseudo_legal_reason(Move::new_unpromote(
+            Square::SQ53,
+            Square::SQ52,
+            Piece::B_PAWN
+        )),
+        Ok(())
+    );
+
+    let sfen = "7nk/7p1/9/7N1/9/9/9/9/4K4 b P 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    assert_eq!(
+        pos.pseudo_legal_reason(Move::new_drop(Piece::B_PAWN, Square::SQ12)),
+        Err(IllegalReason::DropPawnMate)
+    );
+
+    let sfen = "4k4/9/9/9/4r4/9/9/9/4K4 b G 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    assert_eq!(
+        pos.pseudo_legal_reason(Move::new_drop(Piece::B_GOLD, Square::SQ48)),
+        Err(IllegalReason::NotEvasion)
+    );
+    assert_eq!(
+        pos.pseudo_legal_reason(Move::new_unpromote(
+            Square::SQ59,
+            Square::SQ58,
+            Piece::B_KING
+        )),
+        Err(IllegalReason::NotEvasion)
+    );
+    assert_eq!(
+        pos.pseudo_legal_reason(Move::new_drop(Piece::B_GOLD, Square::SQ56)),
+        Ok(())
+    );
+
+    // agrees with pseudo_legal() for all the generated moves.
+    let sfen = "l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w GR5pnsg 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    let mut mlist = MoveList::new();
+    mlist.generate_all::<NonEvasionsType>(&pos, 0);
+    for ext_move in mlist.slice(0) {
+        assert_eq!(
+            pos.pseudo_legal_reason(ext_move.mv).is_ok(),
+            pos.pseudo_legal::<NotSearchingType>(ext_move.mv)
+        );
+    }
+}
+
+#[test]
+fn test_position_check_resolution_squares() {
+    let sfen = "9/4k4/r8/3b5/4L4/9/9/9/4K4 w pnsg 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    let mut expected = Bitboard::ZERO;
+    expected.set(Square::SQ53);
+    expected.set(Square::SQ54);
+    expected.set(Square::SQ55);
+    assert!(pos.check_resolution_squares() == expected);
+
+    // not in check.
+    let pos = Position::new();
+    assert!(!pos.check_resolution_squares().to_bool());
+
+    // double check.
+    let sfen = "9/4k4/9/5N3/4L4/9/9/9/4K4 w - 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    assert_eq!(pos.checkers().count_ones(), 2);
+    assert!(!pos.check_resolution_squares().to_bool());
+}
+
+#[test]
+fn test_position_legal_drop_targets() {
+    for sfen in &[
+        "l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w GR5pnsg 1",
+        "7nk/7p1/9/7N1/9/9/9/9/4K4 b PLN 1",
+        "4k4/9/9/9/4r4/9/9/9/4K4 b PG 1",
+    ] {
+        let pos = Position::new_from_sfen(sfen).unwrap();
+        let mut mlist = MoveList::new();
+        mlist.generate::<LegalType>(&pos, 0);
+        for &pt in PieceType::ALL_HAND.iter() {
+            let num = mlist
+                .slice(0)
+                .iter()
+                .filter(|x| x.mv.is_drop() && x.mv.piece_type_dropped() == pt)
+                .count();
+            assert_eq!(pos.legal_drop_targets(pt).count_ones() as usize, num);
+        }
+    }
+
+    // drop pawn mate and the last rank.
+    let sfen = "7nk/7p1/9/7N1/9/9/9/9/4K4 b P 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    let to_bb = pos.legal_drop_targets(PieceType::PAWN);
+    assert!(!to_bb.is_set(Square::SQ12));
+    assert!(to_bb.is_set(Square::SQ13));
+    assert!(!to_bb.is_set(Square::SQ11));
+    assert!(!pos.legal_drop_targets(PieceType::GOLD).to_bool());
+}
+
+#[test]
+fn test_position_base_minishogi_sfen() {
+    let sfen = "rbsgk/4p/5/P4/KGSBR b - 1";
+    let args = sfen.split_whitespace().collect::<Vec<&str>>();
+    let pos =
+        PositionBase::new_from_sfen_args_with_geometry(&args, BoardGeometry::MINISHOGI).unwrap();
+    assert_eq!(pos.geometry(), BoardGeometry::MINISHOGI);
+    assert_eq!(pos.to_sfen(), sfen);
+    assert_eq!(pos.piece_on(Square::SQ51), Piece::W_ROOK);
+    assert_eq!(pos.piece_on(Square::SQ15), Piece::B_ROOK);
+    assert_eq!(pos.king_square(Color::BLACK), Square::SQ55);
+    assert_eq!(pos.king_square(Color::WHITE), Square::SQ11);
+
+    let sfen = "rbsgk/4p/5/P4/KGSBR b 2P 1";
+    let args = sfen.split_whitespace().collect::<Vec<&str>>();
+    assert!(
+        PositionBase::new_from_sfen_args_with_geometry(&args, BoardGeometry::MINISHOGI).is_err()
+    );
+    // 9x9 sfen isn't minishogi.
+    let args = START_SFEN.split_whitespace().collect::<Vec<&str>>();
+    assert!(
+        PositionBase::new_from_sfen_args_with_geometry(&args, BoardGeometry::MINISHOGI).is_err()
+    );
+
+    let geometry = BoardGeometry::MINISHOGI;
+    assert!(geometry.is_opponent_field(Color::BLACK, Rank::RANK1));
+    assert!(!geometry.is_opponent_field(Color::BLACK, Rank::RANK2));
+    assert!(geometry.is_opponent_field(Color::WHITE, Rank::RANK5));
+    assert!(!geometry.is_opponent_field(Color::WHITE, Rank::RANK4));
+    assert!(!geometry.is_opponent_field(Color::WHITE, Rank::RANK9));
+}
+
+#[test]
+fn test_position_handicap() {
+    let start = Position::new();
+    for &kind in Handicap::ALL.iter() {
+        let pos = Position::handicap(kind);
+        assert_eq!(pos.side_to_move(), Color::WHITE);
+        assert_eq!(pos.to_sfen(), kind.to_sfen());
+        assert!(pos.pieces_c(Color::BLACK) == start.pieces_c(Color::BLACK));
+        let removed = start.pieces_c(Color::WHITE) & !pos.pieces_c(Color::WHITE);
+        assert!(!(pos.pieces_c(Color::WHITE) & !start.pieces_c(Color::WHITE)).to_bool());
+        let expected: &[Square] = match kind {
+            Handicap::Lance => &[Square::SQ11],
+            Handicap::RightLance => &[Square::SQ91],
+            Handicap::Bishop => &[Square::SQ22],
+            Handicap::Rook => &[Square::SQ82],
+            Handicap::RookLance => &[Square::SQ82, Square::SQ11],
+            Handicap::TwoPieces => &[Square::SQ82, Square::SQ22],
+            Handicap::FourPieces => &[Square::SQ82, Square::SQ22, Square::SQ91, Square::SQ11],
+            Handicap::SixPieces => &[
+                Square::SQ82,
+                Square::SQ22,
+                Square::SQ91,
+                Square::SQ11,
+                Square::SQ81,
+                Square::SQ21,
+            ],
+            Handicap::EightPieces => &[
+                Square::SQ82,
+                Square::SQ22,
+                Square::SQ91,
+                Square::SQ11,
+                Square::SQ81,
+                Square::SQ21,
+                Square::SQ71,
+                Square::SQ31,
+            ],
+            Handicap::TenPieces => &[
+                Square::SQ82,
+                Square::SQ22,
+                Square::SQ91,
+                Square::SQ11,
+                Square::SQ81,
+                Square::SQ21,
+                Square::SQ71,
+                Square::SQ31,
+                Square::SQ61,
+                Square::SQ41,
+            ],
+        };
+        assert_eq!(removed.count_ones() as usize, expected.len());
+        for &sq in expected {
+            assert!(removed.is_set(sq));
+        }
+    }
+}
+
+#[test]
+fn test_position_eq_and_hash() {
+    let do_moves = |moves: &[&str]| {
+        let mut pos = Position::new();
+        for move_str in moves {
+            let m = Move::new_from_usi_str(move_str, &pos).unwrap();
+            let gives_check = pos.gives_check(m);
+            pos.do_move(m, gives_check);
+        }
+        pos
+    };
+    let pos0 = do_moves(&["7g7f", "3c3d", "2g2f"]);
+    let pos1 = do_moves(&["2g2f", "3c3d", "7g7f"]);
+    let pos2 = do_moves(&["7g7f", "3c3d", "2g2f", "4a4b", "3i4h", "4b4a", "4h3i"]);
+    let pos3 = do_moves(&["7g7f", "3c3d", "2g2f", "4a4b"]);
+    assert!(pos0 == pos1);
+    assert!(pos0 == pos2);
+    assert!(pos0 != pos3);
+    assert!(pos0 != Position::new());
+
+    let hash = |pos: &Position| {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        pos.hash(&mut hasher);
+        hasher.finish()
+    };
+    assert_eq!(hash(&pos0), hash(&pos1));
+    assert_eq!(hash(&pos0), hash(&pos2));
+
+    let set: std::collections::HashSet<Position> =
+        vec![pos0, pos1, pos2, pos3].into_iter().collect();
+    assert_eq!(set.len(), 2);
+}
+
+#[test]
+fn test_position_snapshot() {
+    let mut pos = Position::new();
+    for move_str in &["7g7f", "3c3d", "8h2b+"] {
+        let m = Move::new_from_usi_str(move_str, &pos).unwrap();
+        let gives_check = pos.gives_check(m);
+        pos.do_move(m, gives_check);
+    }
+    pos.nodes.store(100, Ordering::Relaxed);
+    let mut snapshot = pos.snapshot();
+    assert!(snapshot == pos);
+    assert_eq!(snapshot.to_sfen(), pos.to_sfen());
+    assert_eq!(snapshot.states.len(), 1);
+    assert_eq!(snapshot.nodes_searched(), 0);
+
+    let m = Move::new_from_usi_str("3a2b", &snapshot).unwrap();
+    let gives_check = snapshot.gives_check(m);
+    snapshot.do_move(m, gives_check);
+    assert_eq!(snapshot.states.len(), 2);
+    assert_eq!(pos.states.len(), 4);
+    assert_eq!(pos.nodes_searched(), 100);
+    assert!(snapshot != pos);
+}
+
+#[test]
+fn test_position_is_legal_drop() {
+    let sfen = "7nk/7p1/9/7N1/9/9/4P4/9/4K4 b GLP 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    assert!(pos.is_legal_drop(PieceType::PAWN, Square::SQ13));
+    // two pawns.
+    assert!(!pos.is_legal_drop(PieceType::PAWN, Square::SQ55));
+    // drop pawn mate.
+    assert!(!pos.is_legal_drop(PieceType::PAWN, Square::SQ12));
+    // last rank.
+    assert!(!pos.is_legal_drop(PieceType::LANCE, Square::SQ51));
+    assert!(pos.is_legal_drop(PieceType::GOLD, Square::SQ51));
+    // occupied.
+    assert!(!pos.is_legal_drop(PieceType::GOLD, Square::SQ57));
+    // not in hand.
+    assert!(!pos.is_legal_drop(PieceType::ROOK, Square::SQ55));
+    assert!(!pos.is_legal_drop(PieceType::KING, Square::SQ55));
+
+    // not evasion.
+    let sfen = "4k4/9/9/9/4r4/9/9/9/4K4 b G 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    assert!(!pos.is_legal_drop(PieceType::GOLD, Square::SQ48));
+    assert!(pos.is_legal_drop(PieceType::GOLD, Square::SQ58));
+}
+
+#[test]
+fn test_position_would_be_drop_pawn_mate() {
+    let pos = Position::new_from_sfen("kl7/1n7/K8/9/9/9/9/9/9 b P 1").unwrap();
+    assert!(pos.would_be_drop_pawn_mate(Color::BLACK, Square::SQ92));
+    // not check
+    assert!(!pos.would_be_drop_pawn_mate(Color::BLACK, Square::SQ55));
+    assert!(!pos.would_be_drop_pawn_mate(Color::BLACK, Square::SQ11));
+    // occupied
+    assert!(!pos.would_be_drop_pawn_mate(Color::BLACK, Square::SQ82));
+
+    // check, but the king can escape.
+    let pos = Position::new_from_sfen("k8/9/K8/9/9/9/9/9/9 b P 1").unwrap();
+    assert!(!pos.would_be_drop_pawn_mate(Color::BLACK, Square::SQ92));
+}
+
+#[test]
+fn test_position_is_quiet_check() {
+    let sfen = "4k4/9/4p4/9/4R4/9/6B2/9/4K4 b - 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    // capture
+    let m = Move::new_from_usi_str("5e5c", &pos).unwrap();
+    assert!(pos.gives_check(m));
+    assert!(!pos.is_quiet_check(m));
+    // quiet check
+    let m = Move::new_from_usi_str("3g1e", &pos).unwrap();
+    assert!(pos.gives_check(m));
+    assert!(pos.is_quiet_check(m));
+    // not check
+    let m = Move::new_from_usi_str("5e4e", &pos).unwrap();
+    assert!(!pos.gives_check(m));
+    assert!(!pos.is_quiet_check(m));
+}
+
+#[test]
+fn test_position_gives_check() {
+    const CHECK: bool = true;
+    const NOT_CHECK: bool = false;
+    let array = [
+        (
+            "8k/9/9/9/9/9/9/9/K8 b Rr 1",
+            vec![("R*1b", CHECK), ("R*1h", CHECK), ("R*2b", NOT_CHECK)],
+        ),
+        (
+            "8k/9/9/9/9/9/9/9/K8 w Rr 1",
+            vec![("R*9h", CHECK), ("R*9b", CHECK), ("R*8h", NOT_CHECK)],
+        ),
+        (
+            "8k/9/9/9/9/9/9/8G/K7L b Rr 1",
+            vec![("1h2h", CHECK), ("1h1g", NOT_CHECK)],
+        ),
+    ];
+    for (sfen, move_candidates) in array.iter() {
+        let pos = Position::new_from_sfen(sfen).unwrap();
+        for &(move_str, is_check) in move_candidates {
+            let m = Move::new_from_usi_str(move_str, &pos);
+            assert!(m.is_some());
+            assert_eq!(pos.gives_check(m.unwrap()), is_check);
+        }
+    }
+}
+
+#[test]
+fn test_position_do_move() {
+    let sfen_and_moves_array = [
+        ("4k4/9/9/9/9/9/9/9/4K4 b Bb 1", vec!["B*5g", "B*5c"]),
+        (
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1",
+            vec![
+                "7g7f", "3c3d", "2g2f", "5c5d", "5g5f", "2b8h+", "7i8h", "B*5g", "B*5c", "8b5b",
+                "5c8f+", "5a6b", "3i4h", "5g2d+", "8h7g", "5d5e", "2f2e", "2d3e", "5f5e", "5b5e",
+                "P*5g", "7a7b", "7g6f", "5e5a", "3g3f", "3e4d", "2e2d", "2c2d", "2h2d", "3a3b",
+                "5i6h", "6b7a", "4g4f", "P*5f", "5g5f", "5a5f", "4i5h", "P*2c", "2d2g", "5f5h+",
+                "6i5h", "G*8h", "8i7g", "8h9i", "7g6e", "L*5a", "P*5e", "5a5e", "5h4g", "P*5f",
+                "P*5h", "9i9h", "2g2h", "4a5b", "R*3a",
+            ],
+        ),
+    ];
+    for (sfen, moves) in sfen_and_moves_array.iter() {
+        let mut pos = Position::new_from_sfen(sfen).unwrap();
+        for move_str in moves {
+            let m = Move::new_from_usi_str(move_str, &pos);
+            assert!(m.is_some());
+            let m = m.unwrap();
+            let gives_check = pos.gives_check(m);
+            {
+                // Checking Position::do_move and Position::undo_move work accurately.
+                // (Position::do_move and Position::undo_move call is_ok().)
+                pos.do_move(m, gives_check);
+                pos.undo_move(m);
+            }
+            pos.do_move(m, gives_check);
+            assert!(pos.is_repetition() == Repetition::Not);
+        }
+    }
+}
+
+#[test]
+fn test_position_key_after_null() {
+    let sfens = [
+        "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1",
+        "l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w RGgsn5p 1",
+    ];
+    for sfen in sfens.iter() {
+        let mut pos = Position::new_from_sfen(sfen).unwrap();
+        let key = pos.key();
+        let key_after_null = pos.key_after_null();
+        assert!(key_after_null != key);
+        pos.do_null_move();
+        assert!(pos.key() == key_after_null);
+        assert!(pos.key_after_null() == key);
+        pos.undo_null_move();
+        assert!(pos.key() == key);
+    }
+}
+
+#[test]
+fn test_position_do_moves() {
+    let sfen = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1";
+    let move_strs = [
+        "7g7f", "3c3d", "2g2f", "5c5d", "5g5f", "2b8h+", "7i8h", "B*5g", "B*5c", "8b5b", "5c8f+",
+        "5a6b", "3i4h", "5g2d+", "8h7g", "5d5e", "2f2e", "2d3e", "5f5e", "5b5e",
+    ];
+    let mut pos = Position::new_from_sfen(sfen).unwrap();
+    let mut moves = vec![];
+    let mut sfens = vec![pos.to_sfen()];
+    for move_str in move_strs.iter() {
+        let m = Move::new_from_usi_str(move_str, &pos).unwrap();
+        let gives_check = pos.gives_check(m);
+        pos.do_move(m, gives_check);
+        moves.push(m);
+        sfens.push(pos.to_sfen());
+    }
+    let mut pos = Position::new_from_sfen(sfen).unwrap();
+    pos.do_moves(&moves);
+    assert_eq!(pos.to_sfen(), sfens[moves.len()]);
+    pos.undo_moves(5);
+    assert_eq!(pos.to_sfen(), sfens[moves.len() - 5]);
+    pos.undo_moves(moves.len() - 5);
+    assert_eq!(pos.to_sfen(), sfens[0]);
+    assert!(pos.key() == Position::new_from_sfen(sfen).unwrap().key());
+}
+
+#[test]
+fn test_position_validate_line() {
+    let mut pos = Position::new();
+    let mut moves = vec![];
+    for move_str in ["7g7f", "3c3d", "8h2b+"].iter() {
+        let m = Move::new_from_usi_str(move_str, &pos).unwrap();
+        let gives_check = pos.gives_check(m);
+        pos.do_move(m, gives_check);
+        moves.push(m);
+    }
+    pos.undo_moves(moves.len());
+    assert_eq!(pos.validate_line(&moves), 3);
+    // The pawn on 3c has already moved.
+    moves.push(Move::new_unpromote(
+        Square::SQ33,
+        Square::SQ34,
+        Piece::W_PAWN,
+    ));
+    moves.push(Move::new_unpromote(
+        Square::SQ31,
+        Square::SQ22,
+        Piece::W_SILVER,
+    ));
+    assert_eq!(pos.validate_line(&moves), 3);
+    assert!(pos == Position::new());
+    assert_eq!(pos.ply(), 1);
+    // Wrong side to move.
+    assert_eq!(pos.validate_line(&moves[1..]), 0);
+    assert_eq!(pos.validate_line(&[]), 0);
+}
+
+#[test]
+fn test_position_board_sfen_and_hands_sfen() {
+    let sfens = [
+        "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1",
+        "l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w RGgsn5p 1",
+        "6n1l/2+S1k4/2lp4p/1np1B2b1/3PP4/1N1S3rP/1P2+pPP+p1/1p1G5/3KG2r1 b GSN2L4Pgs2p 399",
+    ];
+    for sfen in sfens.iter() {
+        let pos = Position::new_from_sfen(sfen).unwrap();
+        let side_to_move = if pos.side_to_move() == Color::BLACK {
+            "b"
+        } else {
+            "w"
+        };
+        let joined = format!(
+            "{} {} {} {}",
+            pos.board_sfen(),
+            side_to_move,
+            pos.hands_sfen(),
+            pos.ply()
+        );
+        assert_eq!(&joined, sfen);
+        assert_eq!(joined, pos.to_sfen());
+    }
+    let pos = Position::new();
+    assert_eq!(
+        pos.board_sfen(),
+        "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL"
+    );
+    assert_eq!(pos.hands_sfen(), "-");
+}
+
+#[test]
+fn test_position_set_from_sfen() {
+    let sfens = [
+        "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1",
+        "l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w RGgsn5p 1",
+        "6n1l/2+S1k4/2lp4p/1np1B2b1/3PP4/1N1S3rP/1P2+pPP+p1/1p1G5/3KG2r1 b GSN2L4Pgs2p 399",
+    ];
+    let mut pos = Position::new();
+    let m = Move::new_from_usi_str("7g7f", &pos).unwrap();
+    pos.do_move(m, false);
+    let capacity = pos.states.capacity();
+    for sfen in sfens.iter() {
+        pos.set_from_sfen(sfen).unwrap();
+        let fresh = Position::new_from_sfen(sfen).unwrap();
+        assert_eq!(pos.to_sfen(), fresh.to_sfen());
+        assert!(pos.key() == fresh.key());
+        assert_eq!(pos.material(), fresh.material());
+        assert_eq!(pos.eval_list(), fresh.eval_list());
+        assert_eq!(pos.states.len(), 1);
+        assert_eq!(pos.states.capacity(), capacity);
+        assert!(pos.is_ok());
+    }
+    // An invalid SFEN doesn't change the position.
+    assert!(pos.set_from_sfen("invalid").is_err());
+    assert_eq!(pos.to_sfen(), sfens[2]);
+}
+
+#[test]
+fn test_brute_force_pseudo_legal() {
+    let mut rng: StdRng = SeedableRng::seed_from_u64(20190224);
+    for _ in 0..100 {
+        let max_plies = rng.gen_range(0, 200);
+        let pos = random_legal_position(&mut rng, max_plies);
+        let mut mlist = MoveList::new();
+        let mut expected = if pos.in_check() {
+            // Evasions may include the king moves to the squares attacked by the sliders behind
+            // the king. legal() rejects them.
+            mlist.generate_evasions(&pos, 0);
+            mlist
+                .slice(0)
+                .iter()
+                .map(|x| x.mv)
+                .filter(|&m| pos.legal(m))
+                .collect::<Vec<_>>()
+        } else {
+            mlist.generate_all::<NonEvasionsType>(&pos, 0);
+            mlist.slice(0).iter().map(|x| x.mv).collect::<Vec<_>>()
+        };
+        let mut actual = pos.brute_force_pseudo_legal();
+        if pos.in_check() {
+            actual.retain(|&m| pos.legal(m));
+        }
+        expected.sort_by_key(|m| m.to_u16());
+        actual.sort_by_key(|m| m.to_u16());
+        assert_eq!(actual, expected, "{}", pos.to_sfen());
+    }
+}
+
+#[test]
+fn test_position_from_usi_moves() {
+    let pos = Position::from_usi_moves(&["7g7f", "3c3d", "8h2b+", "3a2b", "B*4e"]).unwrap();
+    assert_eq!(
+        pos.to_sfen(),
+        "lnsgkg1nl/1r5s1/pppppp1pp/6p2/5B3/2P6/PP1PPPPPP/7R1/LNSGKGSNL w b 6"
+    );
+    assert!(Position::from_usi_moves(&[]).unwrap() == Position::new());
+    match Position::from_usi_moves(&["7g7f", "7g7f"]) {
+        Err(SfenError::IllegalMove { move_str }) => assert_eq!(move_str, "7g7f"),
+        _ => panic!(),
+    }
+    match Position::from_usi_moves(&["7g7f", "xxxx"]) {
+        Err(SfenError::IllegalMove { move_str }) => assert_eq!(move_str, "xxxx"),
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn test_position_leaves_own_king_in_check() {
+    // The silver on 5h is pinned by the rook.
+    let sfen = "4r3k/9/9/9/9/9/9/4S4/4K4 b - 1";
+    let pos = Position::new_from_sfen(sfen).unwrap();
+    let m = Move::new_unpromote(Square::SQ58, Square::SQ47, Piece::B_SILVER);
+    assert!(pos.leaves_own_king_in_check(m));
+    assert!(!pos.legal(m));
+    let m = Move::new_from_usi_str("5h5g", &pos).unwrap();
+    assert!(!pos.leaves_own_king_in_check(m));
+    let m = Move::new_from_usi_str("5i4i", &pos).unwrap();
+    assert!(!pos.leaves_own_king_in_check(m));
+
+    let mut rng: StdRng = SeedableRng::seed_from_u64(20190224);
+    for _ in 0..50 {
+        let max_plies = rng.gen_range(0, 200);
+        let pos = random_legal_position(&mut rng, max_plies);
+        let mut mlist = MoveList::new();
+        if pos.in_check() {
+            mlist.generate_evasions(&pos, 0);
+        } else {
+            mlist.generate_all::<NonEvasionsType>(&pos, 0);
+        }
+        for ext_move in mlist.slice(0) {
+            let m = ext_move.mv;
+            assert_eq!(
+                pos.legal(m),
+                !pos.leaves_own_king_in_check(m),
+                "{} {}",
+                pos.to_sfen(),
+                m.to_usi_string()
+            );
+        }
+    }
+}
+
+#[test]
+fn test_position_is_try_win() {
+    assert!(!Position::new().is_try_win());
+
+    let pos = Position::new_from_sfen("4K4/9/9/9/9/9/9/9/k8 b - 1").unwrap();
+    assert!(pos.is_try_win());
+    // The white king isn't on 5i.
+    let pos = Position::new_from_sfen("4K4/9/9/9/9/9/9/9/k8 w - 1").unwrap();
+    assert!(!pos.is_try_win());
+    // The black king can be captured.
+    let pos = Position::new_from_sfen("4K3r/9/9/9/9/9/9/9/k8 b - 1").unwrap();
+    assert!(!pos.is_try_win());
+    let pos = Position::new_from_sfen("8K/9/9/9/9/9/9/9/4k4 w - 1").unwrap();
+    assert!(pos.is_try_win());
+    let pos = Position::new_from_sfen("8K/9/9/9/9/9/9/9/4k3R w - 1").unwrap();
+    assert!(!pos.is_try_win());
+    let pos = Position::new_from_sfen("8K/9/9/9/9/9/9/4k4/9 w - 1").unwrap();
+    assert!(!pos.is_try_win());
+}
+
+#[test]
+fn test_position_is_max_moves_draw() {
+    let pos = Position::new_from_sfen("4k4/9/9/9/9/9/9/9/4K4 b G 256").unwrap();
+    assert!(!pos.is_max_moves_draw(257));
+    assert!(pos.is_max_moves_draw(256));
+    assert!(pos.is_max_moves_draw(255));
+}
+
+#[test]
+fn test_position_fill_legal() {
+    let mut buf = MoveList::new();
+    Position::new().fill_legal(&mut buf);
+    assert_eq!(buf.size, 30);
+    // The moves of the previous position are dropped.
+    let pos = Position::new_from_sfen("4k4/9/9/9/9/9/9/9/8K b - 1").unwrap();
+    pos.fill_legal(&mut buf);
+    assert_eq!(buf.size, 3);
+    assert!(buf
+        .slice(0)
+        .iter()
+        .all(|x| PieceType::new(x.mv.piece_moved_before_move()) == PieceType::KING));
+}
+
+#[test]
+fn test_position_game_result() {
+    const STACK_SIZE: usize = 128 * 1024 * 1024;
+    std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(|| {
+            assert_eq!(Position::new().game_result(), None);
+
+            // checkmate
+            let pos = Position::new_from_sfen("4k4/4G4/4P4/9/9/9/9/9/4K4 w - 1").unwrap();
+            assert_eq!(pos.game_result(), Some(GameResult::BlackWin));
+
+            // sennichite
+            let mut pos = Position::new_from_sfen("4k4/9/9/9/9/9/9/9/4K4 b - 1").unwrap();
+            let cycle = ["5i4i", "5a4a", "4i5i", "4a5a"];
+            for (i, move_str) in cycle.iter().cycle().take(cycle.len() * 3).enumerate() {
+                assert_eq!(pos.game_result(), None);
+                let m = Move::new_from_usi_str(move_str, &pos).unwrap();
+                let gives_check = pos.gives_check(m);
+                pos.do_move(m, gives_check);
+                if i + 1 < cycle.len() * 3 {
+                    assert_eq!(pos.game_result(), None);
+                }
+            }
+            assert_eq!(pos.game_result(), Some(GameResult::Draw));
+
+            // perpetual check
+            let mut pos = Position::new_from_sfen("8k/9/9/9/9/9/9/9/4K2R1 b - 1").unwrap();
+            let cycle = ["2i1i", "1a2a", "1i2i", "2a1a"];
+            for move_str in cycle.iter().cycle().take(cycle.len() * 3) {
+                let m = Move::new_from_usi_str(move_str, &pos).unwrap();
+                let gives_check = pos.gives_check(m);
+                pos.do_move(m, gives_check);
+            }
+            assert_eq!(pos.game_result(), Some(GameResult::WhiteWin));
+
+            // entering king
+            let pos = Position::new_from_sfen("1p7/KRRBBPPPP/NN7/9/9/9/9/9/8k b 2P 1").unwrap();
+            assert_eq!(pos.game_result(), Some(GameResult::BlackWin));
+            let pos = Position::new_from_sfen("K8/9/9/9/9/9/nn7/krrbbpppp/1P7 w p 2").unwrap();
+            assert_eq!(pos.game_result(), Some(GameResult::WhiteWin));
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn test_position_attacked_by() {
+    let pos = Position::new();
+    let black = pos.attacked_by(Color::BLACK);
+    let white = pos.attacked_by(Color::WHITE);
+    let rank6 = Bitboard::rank_mask(Rank::RANK6);
+    let rank4 = Bitboard::rank_mask(Rank::RANK4);
+    assert!((black & rank6) == rank6);
+    assert!((white & rank4) == rank4);
+    assert!(!(black & Bitboard::in_front_mask(Color::BLACK, Rank::RANK6)).to_bool());
+    assert!(!(white & Bitboard::in_front_mask(Color::WHITE, Rank::RANK4)).to_bool());
+
+    let sfen = "l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w RGgsn5p 1";
+    for sfen in &[START_SFEN, sfen] {
+        let pos = Position::new_from_sfen(sfen).unwrap();
+        for &c in Color::ALL.iter() {
+            let attacked = pos.attacked_by(c);
+            for sq in Square::ALL.iter() {
+                assert_eq!(
+                    attacked.is_set(*sq),
+                    pos.attackers_to(c, *sq, &pos.occupied_bb()).to_bool()
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_position_mobility() {
+    let pos = Position::new();
+    // 9 pawns, 2 lances, 2 silvers, 2 golds and the rook.
+    assert_eq!(pos.mobility(Color::BLACK), 9 + 2 + 2 * 2 + 3 * 2 + 6);
+    assert_eq!(pos.mobility(Color::BLACK), pos.mobility(Color::WHITE));
+
+    // The king isn't counted. The rook can capture on 5c but can't move beyond it.
+    let pos = Position::new_from_sfen("4k4/9/4p4/9/9/9/9/9/4R3K b - 1").unwrap();
+    assert_eq!(pos.mobility(Color::BLACK), 6 + 7);
+    assert_eq!(pos.mobility(Color::WHITE), 1);
+}
+
+#[test]
+fn test_position_king_zone_attackers() {
+    let pos = Position::new();
+    assert_eq!(pos.king_zone_attackers(Color::BLACK), 0);
+    assert_eq!(pos.king_zone_attackers(Color::WHITE), 0);
+
+    // The rook, the bishop and the knight attack the squares around the black king.
+    // The pawn on 1c and the white king don't.
+    let pos = Position::new_from_sfen("4k4/9/8p/9/9/4n4/9/2b6/r3K4 b - 1").unwrap();
+    assert_eq!(pos.king_zone_attackers(Color::BLACK), 3);
+    assert_eq!(pos.king_zone_attackers(Color::WHITE), 0);
+}
+
+#[test]
+fn test_position_count() {
+    let pos = Position::new();
+    assert_eq!(pos.count_all(PieceType::PAWN), 18);
+    assert_eq!(pos.count(Color::BLACK, PieceType::PAWN), 9);
+    assert_eq!(pos.count(Color::BLACK, PieceType::KING), 1);
+    assert_eq!(pos.count(Color::WHITE, PieceType::KING), 1);
+    assert_eq!(pos.count(Color::WHITE, PieceType::SILVER), 2);
+    assert_eq!(pos.count_all(PieceType::DRAGON), 0);
+
+    // Hand pieces are not counted.
+    let pos = Position::new_from_sfen("4k4/9/9/9/9/9/9/9/4K4 b S2P 1").unwrap();
+    assert_eq!(pos.count(Color::BLACK, PieceType::SILVER), 0);
+    assert_eq!(pos.count_all(PieceType::PAWN), 0);
+}
+
+#[test]
+fn test_position_pieces() {
+    let pos = Position::new();
+    assert_eq!(pos.pieces().count(), 40);
+    assert!(pos
+        .pieces()
+        .all(|(sq, pc)| pc != Piece::EMPTY && pos.piece_on(sq) == pc));
+    assert_eq!(
+        pos.pieces().filter(|&(_, pc)| pc == Piece::B_PAWN).count(),
+        pos.count(Color::BLACK, PieceType::PAWN) as usize
+    );
+
+    let pos = Position::new_from_sfen("4k4/9/9/9/9/9/9/9/4K4 b S2P 1").unwrap();
+    let mut pieces = pos.pieces().collect::<Vec<_>>();
+    pieces.sort_by_key(|&(sq, _)| sq.0);
+    assert_eq!(
+        pieces,
+        vec![(Square::SQ51, Piece::W_KING), (Square::SQ59, Piece::B_KING)]
+    );
+}
+
+#[test]
+fn test_position_diff() {
+    let pos = Position::new();
+    assert!(pos.diff(&pos).is_empty());
+
+    let sfen = "4k4/9/4p4/9/9/9/9/9/1K2R4 b - 1";
+    let before = Position::new_from_sfen(sfen).unwrap();
+    let mut after = Position::new_from_sfen(sfen).unwrap();
+    let m = Move::new_from_usi_str("5i5c", &after).unwrap();
+    let gives_check = after.gives_check(m);
+    after.do_move(m, gives_check);
+    let diff = before.diff(&after);
+    assert_eq!(
+        diff.squares,
+        vec![
+            (Square::SQ53, Piece::W_PAWN, Piece::B_ROOK),
+            (Square::SQ59, Piece::B_ROOK, Piece::EMPTY),
+        ]
+    );
+    assert_eq!(diff.hands, vec![(Color::BLACK, PieceType::PAWN, 1)]);
+    let diff = after.diff(&before);
+    assert_eq!(diff.hands, vec![(Color::BLACK, PieceType::PAWN, -1)]);
+}
+
+#[test]
+fn test_random_legal_position() {
+    let mut rng: StdRng = SeedableRng::seed_from_u64(20190224);
+    let mut sfens = vec![];
+    for _ in 0..100 {
+        let pos = random_legal_position(&mut rng, 256);
+        assert!(pos.is_ok());
+        assert!(pos.ply() <= 257);
+        let sfen = pos.to_sfen();
+        let parsed = Position::new_from_sfen(&sfen).unwrap();
+        assert!(parsed.key() == pos.key());
+        sfens.push(sfen);
+    }
+    // The same seed generates the same positions.
+    let mut rng: StdRng = SeedableRng::seed_from_u64(20190224);
+    for sfen in sfens.iter() {
+        assert_eq!(&random_legal_position(&mut rng, 256).to_sfen(), sfen);
+    }
+    assert!(random_legal_position(&mut rng, 0) == Position::new());
+}
+
+#[test]
+fn test_do_undo_consistency() {
+    let mut rng: StdRng = SeedableRng::seed_from_u64(20190224);
+    for _ in 0..50 {
+        let max_plies = rng.gen_range(0, 200);
+        let mut pos = random_legal_position(&mut rng, max_plies);
+        let mut mlist = MoveList::new();
+        mlist.generate::<LegalType>(&pos, 0);
+        for ext_move in mlist.slice(0) {
+            assert_do_undo_consistency(&mut pos, ext_move.mv);
+        }
+        assert!(pos.is_ok());
+    }
+}
+
+#[test]
+fn test_golds_bb() {
+    let golds = |pos: &Position| {
+        pos.pieces_ppppp(
+            PieceType::GOLD,
+            PieceType::PRO_PAWN,
+            PieceType::PRO_LANCE,
+            PieceType::PRO_KNIGHT,
+            PieceType::PRO_SILVER,
+        )
+    };
+    // promotions to gold moving pieces, and captures of them.
+    let mut pos = Position::new_from_sfen("4k4/9/2P3N2/9/9/9/9/9/4K4 b - 1").unwrap();
+    for move_str in ["7c7b+", "5a4b", "3c2a+", "4b3b", "7b6b", "3b2a"].iter() {
+        let m = Move::new_from_usi_str(move_str, &pos).unwrap();
+        let gives_check = pos.gives_check(m);
+        pos.do_move(m, gives_check);
+        assert!(pos.pieces_golds() == golds(&pos));
+    }
+
+    let mut rng: StdRng = SeedableRng::seed_from_u64(20190224);
+    let mut promotions = 0;
+    for _ in 0..20 {
+        let mut pos = Position::new();
+        let mut moves = vec![];
+        for _ in 0..512 {
+            if pos.game_result().is_some() {
+                break;
+            }
+            let mut mlist = MoveList::new();
+            mlist.generate::<LegalType>(&pos, 0);
+            let m = mlist.slice(0)[rng.gen_range(0, mlist.size)].mv;
+            if m.is_promotion() {
+                promotions += 1;
+            }
+            let gives_check = pos.gives_check(m);
+            pos.do_move(m, gives_check);
+            moves.push(m);
+            assert!(pos.pieces_golds() == golds(&pos), "{}", pos.to_sfen());
+        }
+        for m in moves.iter().rev() {
+            pos.undo_move(*m);
+            assert!(pos.pieces_golds() == golds(&pos), "{}", pos.to_sfen());
+        }
+    }
+    assert!(promotions > 0);
+}
+
+#[test]
+fn test_key_from_scratch() {
+    let mut rng: StdRng = SeedableRng::seed_from_u64(20190224);
+    for _ in 0..10 {
+        let max_plies = rng.gen_range(0, 100);
+        let mut pos = random_legal_position(&mut rng, max_plies);
+        assert!(pos.key() == pos.key_from_scratch());
+        for _ in 0..512 {
+            if pos.game_result().is_some() {
+                break;
+            }
+            let mut mlist = MoveList::new();
+            mlist.generate::<LegalType>(&pos, 0);
+            let m = mlist.slice(0)[rng.gen_range(0, mlist.size)].mv;
+            let gives_check = pos.gives_check(m);
+            pos.do_move(m, gives_check);
+            assert!(pos.key() == pos.key_from_scratch(), "{}", pos.to_sfen());
+            if !pos.in_check() {
+                pos.do_null_move();
+                assert!(pos.key() == pos.key_from_scratch(), "{}", pos.to_sfen());
+                pos.undo_null_move();
+            }
+        }
+    }
+}
+
+#[test]
+fn test_check_info_new() {
+    // CheckInfo::check_squares in CheckInfo::new() depends on the following assumptions.
+    assert_eq!(0, PieceType::OCCUPIED.0);
+    assert_eq!(1, PieceType::PAWN.0);
+    assert_eq!(2, PieceType::LANCE.0);
+    assert_eq!(3, PieceType::KNIGHT.0);
+    assert_eq!(4, PieceType::SILVER.0);
+    assert_eq!(5, PieceType::BISHOP.0);
+    assert_eq!(6, PieceType::ROOK.0);
+    assert_eq!(7, PieceType::GOLD.0);
+    assert_eq!(8, PieceType::KING.0);
+    assert_eq!(9, PieceType::PRO_PAWN.0);
     assert_eq!(10, PieceType::PRO_LANCE.0);
     assert_eq!(11, PieceType::PRO_KNIGHT.0);
     assert_eq!(12, PieceType::PRO_SILVER.0);
@@ -3222,6 +5445,54 @@ fn test_huffman_code() {
     }
 }
 
+#[test]
+fn test_legal_moves_cache() {
+    let mut pos = Position::new();
+    let mut cache = LegalMovesCache::new();
+    let ptr = cache.legal_moves(&pos).as_ptr();
+    assert_eq!(cache.legal_moves(&pos).len(), 30);
+    assert_eq!(cache.legal_moves(&pos).as_ptr(), ptr);
+
+    let m = Move::new_from_usi_str("7g7f", &pos).unwrap();
+    assert!(cache.legal_moves(&pos).contains(&m));
+    pos.do_move(m, false);
+    let moves = cache.legal_moves(&pos).to_vec();
+    assert!(!moves.contains(&m));
+    let mut mlist = MoveList::new();
+    mlist.generate::<LegalType>(&pos, 0);
+    assert_eq!(moves.len(), mlist.size);
+
+    pos.undo_move(m);
+    assert!(cache.legal_moves(&pos).contains(&m));
+
+    // The key doesn't change, but the legal moves do.
+    let mut pos = Position::new_from_sfen("kl7/1n7/K8/9/9/9/9/9/9 b P 1").unwrap();
+    let drop_pawn_mate = Move::new_drop(Piece::B_PAWN, Square::SQ92);
+    assert!(!cache.legal_moves(&pos).contains(&drop_pawn_mate));
+    pos.set_allow_drop_pawn_mate(true);
+    assert!(cache.legal_moves(&pos).contains(&drop_pawn_mate));
+}
+
+#[test]
+fn test_is_impasse_draw() {
+    let pos = Position::new_from_sfen("K8/9/9/9/9/9/9/9/8k b - 1").unwrap();
+    assert!(pos.is_impasse_draw());
+    let pos = Position::new_from_sfen("KPG6/9/9/9/9/9/9/6rgp/8k w R2BG 1").unwrap();
+    assert_eq!(pos.declaration_points(Color::BLACK), 2 + 16);
+    assert_eq!(pos.declaration_points(Color::WHITE), 7);
+    assert!(pos.is_impasse_draw());
+    // black has 28 points.
+    let pos = Position::new_from_sfen("KPG6/9/9/9/9/9/9/6rgp/8k w R2BG9P 1").unwrap();
+    assert_eq!(pos.declaration_points(Color::BLACK), 28 - 1);
+    assert!(pos.is_impasse_draw());
+    let pos = Position::new_from_sfen("KPG6/9/9/9/9/9/9/6rgp/8k w R2BG10P 1").unwrap();
+    assert_eq!(pos.declaration_points(Color::BLACK), 28);
+    assert!(!pos.is_impasse_draw());
+    // white king isn't entering.
+    let pos = Position::new_from_sfen("K8/9/9/9/9/8k/9/9/9 b - 1").unwrap();
+    assert!(!pos.is_impasse_draw());
+}
+
 #[test]
 fn test_is_entering_king_win() {
     const STACK_SIZE: usize = 128 * 1024 * 1024;
@@ -3340,6 +5611,50 @@ fn test_is_repetition() {
         .unwrap();
 }
 
+#[test]
+fn test_repetition_outcome() {
+    const STACK_SIZE: usize = 128 * 1024 * 1024;
+    std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(|| {
+            let sfen = "8k/9/9/9/9/9/9/9/8K b R2P 1";
+            let moves = [
+                ("P*1b", None),
+                ("1a2a", None),
+                ("1b1a+", None),
+                ("2a1a", Some((Color::WHITE, RepetitionKind::SuperiorHand))),
+                ("P*1b", Some((Color::WHITE, RepetitionKind::SuperiorHand))),
+                ("1a2a", Some((Color::WHITE, RepetitionKind::SuperiorHand))),
+                ("R*2b", None),
+                ("2a3a", None),
+                ("2b3b", None),
+                ("3a2a", None),
+                // black has been checking, so white is favored in both cases.
+                ("3b2b", Some((Color::WHITE, RepetitionKind::PerpetualCheck))),
+                ("2a3a", Some((Color::WHITE, RepetitionKind::PerpetualCheck))),
+            ];
+            let mut pos = Position::new_from_sfen(sfen).unwrap();
+            for (m, outcome) in &moves {
+                let m = Move::new_from_usi_str(m, &pos).unwrap();
+                pos.do_move(m, pos.gives_check(m));
+                assert_eq!(pos.repetition_outcome(), *outcome);
+            }
+
+            let mut pos = Position::new();
+            for m in ["5i5h", "5a5b", "5h5i", "5b5a"].iter() {
+                let m = Move::new_from_usi_str(m, &pos).unwrap();
+                pos.do_move(m, pos.gives_check(m));
+            }
+            assert_eq!(
+                pos.repetition_outcome(),
+                Some((Color::BLACK, RepetitionKind::Draw))
+            );
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
 #[test]
 fn test_mate_move_in_1ply() {
     const STACK_SIZE: usize = 128 * 1024 * 1024;
@@ -3525,3 +5840,112 @@ fn test_effect_bb_of_checker_where_king_cannot_escape() {
         .join()
         .unwrap();
 }
+
+#[test]
+fn test_position_flip() {
+    let sfens = [
+        "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1",
+        "l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w RGgsn5p 1",
+        "l4S2l/4g1gs1/5p1p1/pr2N1pkp/4Gn3/PP3PPPP/2GPP4/1K7/L3r+s2L w BS2N5Pb 20",
+        "6n1l/2+S1k4/2lp4p/1np1B2b1/3PP4/1N1S3rP/1P2+pPP+p1/1p1G5/3KG2r1 b GSN2L4Pgs2p 399",
+    ];
+    for sfen in sfens.iter() {
+        let pos = Position::new_from_sfen(sfen).unwrap();
+        let flipped = pos.flip();
+        assert_eq!(flipped.side_to_move(), pos.side_to_move().inverse());
+        assert_eq!(
+            flipped.king_square(Color::BLACK),
+            pos.king_square(Color::WHITE).inverse()
+        );
+        assert_eq!(flipped.flip().to_sfen(), pos.to_sfen());
+    }
+    let pos = Position::new();
+    assert_eq!(
+        pos.flip().to_sfen(),
+        "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1"
+    );
+}
+
+#[test]
+fn test_position_set_piece_values() {
+    let sfen = "lnsgkgsnl/7b1/ppppppppp/9/9/9/PPPPPPPPP/1B7/LNSGKGSNL b R 1";
+    let mut pos = Position::new_from_sfen(sfen).unwrap();
+    assert_eq!(pos.material(), piece_type_value(PieceType::ROOK));
+
+    let mut piece_values = PieceValues::new();
+    piece_values.set_piece_type_value(PieceType::ROOK, Value(2000));
+    pos.set_piece_values(piece_values);
+    assert_eq!(pos.material(), Value(2000));
+
+    // material is kept through do_move().
+    let m = Move::new_from_usi_str("R*5e", &pos).unwrap();
+    let gives_check = pos.gives_check(m);
+    pos.do_move(m, gives_check);
+    assert_eq!(pos.material(), Value(2000));
+    let m = Move::new_from_usi_str("5a4b", &pos).unwrap();
+    let gives_check = pos.gives_check(m);
+    pos.do_move(m, gives_check);
+    // captures a pawn and promotes the rook.
+    let m = Move::new_from_usi_str("5e5c+", &pos).unwrap();
+    let gives_check = pos.gives_check(m);
+    pos.do_move(m, gives_check);
+    assert_eq!(
+        pos.material(),
+        Value(2000)
+            + piece_values.capture_piece_type_value(PieceType::PAWN)
+            + piece_values.promote_piece_type_value(PieceType::ROOK)
+    );
+    assert_eq!(
+        pos.material(),
+        StateInfo::new_material(&pos.base, &pos.piece_values)
+    );
+
+    // The material of the previous states is recalculated too.
+    piece_values.set_piece_type_value(PieceType::PAWN, Value(300));
+    piece_values.set_piece_type_value(PieceType::DRAGON, Value(3000));
+    pos.set_piece_values(piece_values);
+    for _ in 0..3 {
+        assert_eq!(
+            pos.material(),
+            StateInfo::new_material(&pos.base, &pos.piece_values)
+        );
+        pos.undo_moves(1);
+    }
+    assert_eq!(pos.material(), Value(2000));
+
+    let pos2 = Position::new_from_position(&pos, Arc::new(AtomicI64::new(0)));
+    assert_eq!(
+        pos2.piece_values().piece_type_value(PieceType::ROOK),
+        Value(2000)
+    );
+}
+
+#[test]
+fn test_position_has_single_evasion() {
+    // Only 1a2b.
+    let pos = Position::new_from_sfen("8k/7G1/9/9/9/9/9/9/K8 w - 1").unwrap();
+    assert!(pos.has_single_evasion());
+    // Mate.
+    let pos = Position::new_from_sfen("8k/7G1/7P1/9/9/9/9/9/K8 w - 1").unwrap();
+    assert!(!pos.has_single_evasion());
+    // 1a2a and 1a2b.
+    let pos = Position::new_from_sfen("8k/9/9/9/9/9/9/9/K7R w - 1").unwrap();
+    assert!(!pos.has_single_evasion());
+}
+
+#[test]
+fn test_position_has_non_pawn_material() {
+    let pos = Position::new_from_sfen("4k4/9/ppp6/9/9/9/6PPP/9/4K4 b RB2Pg 1").unwrap();
+    // Pieces in hand aren't counted.
+    assert!(!pos.has_non_pawn_material(Color::BLACK));
+    assert!(!pos.has_non_pawn_material(Color::WHITE));
+
+    let pos = Position::new();
+    assert!(pos.has_non_pawn_material(Color::BLACK));
+    assert!(pos.has_non_pawn_material(Color::WHITE));
+
+    // A promoted pawn isn't a pawn.
+    let pos = Position::new_from_sfen("4k4/9/9/9/9/9/9/+P8/4K4 b - 1").unwrap();
+    assert!(pos.has_non_pawn_material(Color::BLACK));
+    assert!(!pos.has_non_pawn_material(Color::WHITE));
+}
@@ -2,6 +2,7 @@ use crate::evaluate::*;
 use crate::movegen::*;
 use crate::movepick::*;
 use crate::position::*;
+use crate::thread::*;
 use crate::tt::*;
 use crate::types::*;
 
@@ -38,6 +39,14 @@ impl LimitsType {
             start_time: None,
         }
     }
+    // "go depth n" which starts now.
+    #[cfg(test)]
+    pub fn with_depth(depth: u32) -> LimitsType {
+        let mut limits = LimitsType::new();
+        limits.depth = Some(depth);
+        limits.start_time = Some(std::time::Instant::now());
+        limits
+    }
     pub fn use_time_management(&self) -> bool {
         self.mate.is_none()
             && self.movetime.is_none()
@@ -48,6 +57,29 @@ impl LimitsType {
     }
 }
 
+// Follow the TT moves from pos. pos is restored before returning.
+#[allow(dead_code)]
+pub fn extract_pv(pos: &mut Position, tt: &TranspositionTable, max_len: usize) -> Vec<Move> {
+    let mut pv = vec![];
+    while pv.len() < max_len {
+        let m = match tt.peek(pos.key()).and_then(|tte| tte.mv(pos)) {
+            Some(m) if pos.legal(m) => m,
+            _ => break,
+        };
+        let gives_check = pos.gives_check(m);
+        pos.do_move(m, gives_check);
+        pv.push(m);
+        if pos.is_repetition() != Repetition::Not {
+            break;
+        }
+    }
+    for m in pv.iter().rev() {
+        pos.undo_move(*m);
+    }
+    debug_assert_eq!(pos.validate_line(&pv), pv.len());
+    pv
+}
+
 #[derive(Clone, Eq)]
 pub struct RootMove {
     pub score: Value,
@@ -115,6 +147,29 @@ impl RootMove {
             pos.undo_move(*m);
         }
     }
+    pub fn extract_ponder_from_tt(
+        &mut self,
+        pos: &mut Position,
+        tt: *mut TranspositionTable,
+    ) -> bool {
+        debug_assert!(self.pv.len() == 1);
+        let m = self.pv[0];
+        let gives_check = pos.gives_check(m);
+        pos.do_move(m, gives_check);
+        let (tte, tt_hit) = unsafe { (*tt).probe(pos.key()) };
+        if tt_hit {
+            if let Some(ponder_move) = tte.mv(pos) {
+                // tt_move is only pseudo legal.
+                let mut mlist = MoveList::new();
+                mlist.generate::<LegalType>(pos, 0);
+                if mlist.slice(0).iter().any(|x| x.mv == ponder_move) {
+                    self.pv.push(ponder_move);
+                }
+            }
+        }
+        pos.undo_move(m);
+        self.pv.len() > 1
+    }
 }
 
 pub type RootMoves = Vec<RootMove>;
@@ -128,6 +183,8 @@ pub struct Stack {
     pub killers: [Option<Move>; 2],
     pub static_eval: Value,
     pub static_eval_raw: EvalSum,
+    #[cfg(feature = "nnue")]
+    pub accumulator: crate::nnue::Accumulator,
     pub stat_score: i32,
     pub move_count: i32,
 }
@@ -142,6 +199,8 @@ impl Stack {
             killers: [None, None],
             static_eval: Value::ZERO,
             static_eval_raw: EvalSum::new(),
+            #[cfg(feature = "nnue")]
+            accumulator: crate::nnue::Accumulator::new(),
             stat_score: 0,
             move_count: 0,
         }
@@ -212,6 +271,12 @@ pub fn futility_margin(depth: Depth) -> Value {
     Value(75 * depth.0 / Depth::ONE_PLY.0)
 }
 
+// For the futility pruning of quiet moves. lmr_depth_per_one_ply is the reduced depth.
+// base and per_depth are read from "Futility_Margin_Base" and "Futility_Margin_Per_Depth".
+pub fn quiet_futility_margin(lmr_depth_per_one_ply: i32, base: i32, per_depth: i32) -> Value {
+    Value(base + per_depth * lmr_depth_per_one_ply)
+}
+
 pub fn futility_move_count(improving: bool, depth_per_one_ply: i32) -> i32 {
     (5 + depth_per_one_ply * depth_per_one_ply) * (1 + i32::from(improving)) / 2
 }
@@ -226,13 +291,66 @@ lazy_static! {
     };
 }
 
-pub fn reduction(improving: bool, depth: Depth, move_count: i32) -> Depth {
+// The table value of [depth][move_count] is REDUCTIONS[depth] * REDUCTIONS[move_count].
+// scale is percent. Read from "Late_Move_Reduction_Scale".
+pub fn reduction(improving: bool, depth: Depth, move_count: i32, scale: i32) -> Depth {
     let r = unsafe {
         *REDUCTIONS.get_unchecked((depth.0 / Depth::ONE_PLY.0) as usize)
             * *REDUCTIONS.get_unchecked(move_count as usize)
-    };
+    } * scale
+        / 100;
     Depth(((r + 512) / 1024 + i32::from(!improving && r > 1024)) * Depth::ONE_PLY.0)
 }
 
 pub const SKIP_SIZE: [i32; 20] = [1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 4, 4, 4, 4, 4, 4, 4, 4];
 pub const SKIP_PHASE: [i32; 20] = [0, 1, 0, 1, 2, 3, 0, 1, 2, 3, 4, 5, 0, 1, 2, 3, 4, 5, 6, 7];
+
+// Quiescence search of "pos" by a throwaway single thread, TT and EvalHash.
+// This is the qsearch of the main search, so captures and pawn promotions are searched,
+// or all evasions if in check, with the stand pat, futility and SEE pruning.
+#[allow(dead_code)]
+pub fn qsearch(pos: &mut Position, alpha: Value, beta: Value) -> Value {
+    let mut thread_pool = ThreadPool::new();
+    let mut tt = TranspositionTable::new();
+    let mut ehash = EvalHash::new();
+    thread_pool.set(1, &mut tt, &mut ehash);
+    tt.resize(1, &mut thread_pool);
+    ehash.resize(1, &mut thread_pool);
+    thread_pool.qsearch(pos, alpha, beta)
+}
+
+#[test]
+fn test_qsearch() {
+    use crate::usioption::*;
+    const STACK_SIZE: usize = 128 * 1024 * 1024;
+    std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(|| {
+            load_evaluate_files(&UsiOptions::new().get_string("Eval_Dir")).unwrap();
+            // 5f5e takes the rook for nothing.
+            let mut pos = Position::new_from_sfen("4k4/9/9/9/4r4/4P4/9/9/4K4 b - 1").unwrap();
+            let static_eval = evaluate_static(&pos);
+            let value = qsearch(&mut pos, -Value::INFINITE, Value::INFINITE);
+            let expected = {
+                let mut pos = Position::new_from_sfen("4k4/9/9/9/4P4/9/9/9/4K4 w R 2").unwrap();
+                -qsearch(&mut pos, -Value::INFINITE, Value::INFINITE)
+            };
+            assert!(value > static_eval);
+            assert_eq!(value, expected);
+            assert_eq!(pos.to_sfen(), "4k4/9/9/9/4r4/4P4/9/9/4K4 b - 1");
+
+            // Captures aren't searched when the stand pat is enough.
+            let beta = static_eval;
+            assert_eq!(qsearch(&mut pos, beta - Value(1), beta), static_eval);
+
+            // mated.
+            let mut pos = Position::new_from_sfen("4k4/4G4/4P4/9/9/9/9/9/4K4 w - 1").unwrap();
+            assert_eq!(
+                qsearch(&mut pos, -Value::INFINITE, Value::INFINITE),
+                Value::mated_in(0)
+            );
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
@@ -2,7 +2,55 @@ use crate::types::*;
 
 pub const START_SFEN: &str = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1";
 
-#[derive(Debug)]
+// Handicap (komaochi) games. White gives the handicap and moves first.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Handicap {
+    Lance,       // Kyo-ochi
+    RightLance,  // Migi-kyo-ochi
+    Bishop,      // Kaku-ochi
+    Rook,        // Hisha-ochi
+    RookLance,   // Hikyo-ochi
+    TwoPieces,   // Nimai-ochi
+    FourPieces,  // Yonmai-ochi
+    SixPieces,   // Rokumai-ochi
+    EightPieces, // Hachimai-ochi
+    TenPieces,   // Jumai-ochi
+}
+
+impl Handicap {
+    #[allow(dead_code)]
+    pub const ALL: [Handicap; 10] = [
+        Handicap::Lance,
+        Handicap::RightLance,
+        Handicap::Bishop,
+        Handicap::Rook,
+        Handicap::RookLance,
+        Handicap::TwoPieces,
+        Handicap::FourPieces,
+        Handicap::SixPieces,
+        Handicap::EightPieces,
+        Handicap::TenPieces,
+    ];
+    pub fn to_sfen(self) -> &'static str {
+        match self {
+            Handicap::Lance => "lnsgkgsn1/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+            Handicap::RightLance => {
+                "1nsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1"
+            }
+            Handicap::Bishop => "lnsgkgsnl/1r7/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+            Handicap::Rook => "lnsgkgsnl/7b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+            Handicap::RookLance => "lnsgkgsn1/7b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+            Handicap::TwoPieces => "lnsgkgsnl/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+            Handicap::FourPieces => "1nsgkgsn1/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+            Handicap::SixPieces => "2sgkgs2/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+            Handicap::EightPieces => "3gkg3/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+            Handicap::TenPieces => "4k4/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
 pub enum SfenError {
     InvalidNumberOfSections { sections: usize },
     InvalidNumberOfFiles { files: usize },
@@ -10,6 +58,7 @@ pub enum SfenError {
     InvalidNumberOfEmptySquares { empty_squares: i64 },
     InvalidPieceCharactors { chars: String },
     InvalidHandPieceCharactors { chars: String },
+    PromotedPieceInHand { chars: String },
     InvalidNumberOfHandPieces { number: i64 },
     InvalidNumberOfPawns { number: i64 },
     InvalidNumberOfLances { number: i64 },
@@ -22,4 +71,5 @@ pub enum SfenError {
     InvalidGamePly { chars: String },
     SameHandPieceTwice { pt: PieceType },
     KingIsNothing { c: Color },
+    IllegalMove { move_str: String },
 }
@@ -1,13 +1,14 @@
 use crate::evaluate::*;
 use crate::movegen::*;
 use crate::movepick::*;
-use crate::piecevalue::*;
 use crate::position::*;
 use crate::search::*;
 use crate::timeman::*;
 use crate::tt::*;
 use crate::types::*;
 use crate::usioption::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 
@@ -26,6 +27,20 @@ struct Thread {
     capture_history: CapturePieceToHistory,
     continuation_history: ContinuationHistory,
     limits: LimitsType, // Clone from ThreadPool for fast access.
+    // search extensions. Read from usi_options at the beginning of the search.
+    check_extension: Depth,
+    recapture_extension: bool,
+    one_reply_extension: bool,
+    // late move reductions. Read from "Late_Move_Reduction" and "Late_Move_Reduction_Scale".
+    late_move_reduction: bool,
+    reduction_scale: i32,
+    // futility pruning of quiet moves. Read from "Futility_Margin_Base" and "Futility_Margin_Per_Depth".
+    futility_margin_base: i32,
+    futility_margin_per_depth: i32,
+    // The value of the repetition draw for each color. Read from "Contempt".
+    draw_value: [Value; Color::NUM],
+    // The game is a draw at this ply. 0 means no limit. Read from "Max_Moves_To_Draw".
+    max_moves_to_draw: i32,
     tt: *mut TranspositionTable,
     timeman: Arc<Mutex<TimeManagement>>, // shold I use pointer for speedup?
     ehash: *mut EvalHash,
@@ -42,6 +57,8 @@ struct Thread {
     ponder: Arc<AtomicBool>,
     stop: Arc<AtomicBool>,
     nodess: Vec<Arc<AtomicI64>>,
+    // If Some, PV info is sent here instead of being printed.
+    info_sender: Option<std::sync::mpsc::Sender<SearchInfo>>,
 }
 
 unsafe impl std::marker::Send for Thread {} // for Thread::tt
@@ -50,6 +67,53 @@ struct ThreadPoolBase {
     threads: Vec<Arc<Mutex<Thread>>>,
 }
 
+#[allow(dead_code)]
+pub struct SearchResult {
+    pub best_move: Move,
+    pub ponder_move: Option<Move>,
+    pub sel_depth: i32,
+    pub time_ms: i64,
+    pub nps: i64,
+}
+
+// One "info ... pv ..." line.
+pub struct SearchInfo {
+    pub depth: i32,
+    pub seldepth: i32,
+    pub multipv: usize,
+    pub score: Value,
+    pub bound: Bound,
+    pub nodes: i64,
+    pub nps: i64,
+    pub hashfull: i32,
+    pub time_ms: i64,
+    pub pv: Vec<Move>,
+}
+
+impl SearchInfo {
+    pub fn to_usi_string(&self) -> String {
+        format!(
+            "info depth {depth} seldepth {seldepth} multipv {multipv} score {score} {bound}nodes {nodes} nps {nps} hashfull {hashfull} time {time} pv {pv}",
+            depth = self.depth,
+            seldepth = self.seldepth,
+            multipv = self.multipv,
+            score = self.score.to_usi(),
+            bound = if self.bound.0 == Bound::LOWER.0 {
+                "lowerbound "
+            } else if self.bound.0 == Bound::UPPER.0 {
+                "upperbound "
+            } else {
+                ""
+            },
+            nodes = self.nodes,
+            nps = self.nps,
+            hashfull = self.hashfull,
+            time = self.time_ms,
+            pv = self.pv.iter().map(|m| m.to_usi_string()).collect::<Vec<_>>().join(" ")
+        )
+    }
+}
+
 pub struct ThreadPool {
     thread_pool_base: Arc<Mutex<ThreadPoolBase>>,
     nodess: Vec<Arc<AtomicI64>>,
@@ -61,6 +125,10 @@ pub struct ThreadPool {
     pub stop: Arc<AtomicBool>,
     pub limits: LimitsType,
     pub last_best_pv: Arc<Mutex<Vec<Move>>>,
+    pub last_sel_depth: Arc<Mutex<i32>>,
+    // Seeded by Random_Seed at the first search of a game, and dropped by clear().
+    random: Arc<Mutex<Option<(i64, StdRng)>>>,
+    info_sender: Option<std::sync::mpsc::Sender<SearchInfo>>,
     handle: Option<std::thread::JoinHandle<()>>,
 }
 
@@ -68,6 +136,9 @@ impl Thread {
     fn is_main(&self) -> bool {
         self.idx == 0
     }
+    fn is_max_moves_draw(&self) -> bool {
+        self.max_moves_to_draw != 0 && self.position.is_max_moves_draw(self.max_moves_to_draw)
+    }
     fn clear(&mut self) {
         self.calls_count = 0;
         self.counter_moves.fill(None);
@@ -96,6 +167,22 @@ impl Thread {
             self.usi_options.get_i64("MultiPV") as usize,
             self.root_moves.len(),
         );
+        // 0 means that the root is always searched with the full window.
+        let aspiration_delta = self.usi_options.get_i64("Aspiration_Delta") as i32;
+        self.check_extension =
+            Depth(self.usi_options.get_i64("Check_Extension_Plies") as i32 * Depth::ONE_PLY.0);
+        self.recapture_extension = self.usi_options.get_bool("Recapture_Extension");
+        self.one_reply_extension = self.usi_options.get_bool("One_Reply_Extension");
+        self.late_move_reduction = self.usi_options.get_bool("Late_Move_Reduction");
+        self.reduction_scale = self.usi_options.get_i64("Late_Move_Reduction_Scale") as i32;
+        self.futility_margin_base = self.usi_options.get_i64("Futility_Margin_Base") as i32;
+        self.futility_margin_per_depth =
+            self.usi_options.get_i64("Futility_Margin_Per_Depth") as i32;
+        let contempt = Value(self.usi_options.get_i64("Contempt") as i32);
+        let us = self.position.side_to_move();
+        self.draw_value[us.0 as usize] = Value::DRAW - contempt;
+        self.draw_value[us.inverse().0 as usize] = Value::DRAW + contempt;
+        self.max_moves_to_draw = self.usi_options.get_i64("Max_Moves_To_Draw") as i32;
         evaluate_at_root(&self.position, &mut stack);
         while {
             self.root_depth += Depth::ONE_PLY;
@@ -126,9 +213,9 @@ impl Thread {
             self.pv_idx = 0;
             while self.pv_idx < multi_pv && !self.stop.load(Ordering::Relaxed) {
                 self.sel_depth = 0;
-                if self.root_depth >= Depth(5 * Depth::ONE_PLY.0) {
+                if aspiration_delta > 0 && self.root_depth >= Depth(5 * Depth::ONE_PLY.0) {
                     let previous_score = self.root_moves[self.pv_idx].previous_score;
-                    delta = Value(20);
+                    delta = Value(aspiration_delta);
                     alpha = std::cmp::max(previous_score - delta, -Value::INFINITE);
                     beta = std::cmp::min(previous_score + delta, Value::INFINITE);
                 }
@@ -153,16 +240,13 @@ impl Thread {
                             || last_info_time.unwrap().elapsed().as_millis() > 200)
                     {
                         last_info_time = Some(std::time::Instant::now());
-                        println!(
-                            "{}",
-                            self.pv_info_to_usi_string(
-                                self.nodes_searched(),
-                                multi_pv,
-                                self.root_depth,
-                                alpha,
-                                beta,
-                                false,
-                            )
+                        self.send_pv_info(
+                            self.nodes_searched(),
+                            multi_pv,
+                            self.root_depth,
+                            alpha,
+                            beta,
+                            false,
                         );
                     }
                     if best_value <= alpha {
@@ -195,16 +279,13 @@ impl Thread {
                         || last_info_time.unwrap().elapsed().as_millis() > 200)
                 {
                     last_info_time = Some(std::time::Instant::now());
-                    println!(
-                        "{}",
-                        self.pv_info_to_usi_string(
-                            self.nodes_searched(),
-                            multi_pv,
-                            self.root_depth,
-                            alpha,
-                            beta,
-                            false,
-                        )
+                    self.send_pv_info(
+                        self.nodes_searched(),
+                        multi_pv,
+                        self.root_depth,
+                        alpha,
+                        beta,
+                        false,
                     );
                 }
 
@@ -319,6 +400,9 @@ impl Thread {
             // Step 2
             match self.position.is_repetition() {
                 Repetition::Not => {
+                    if self.is_max_moves_draw() {
+                        return self.draw_value[self.position.side_to_move().0 as usize];
+                    }
                     if self.stop.load(Ordering::Relaxed) || get_stack(stack, 0).ply >= MAX_PLY {
                         return if get_stack(stack, 0).ply >= MAX_PLY && !in_check {
                             evaluate(&mut self.position, stack, self.ehash)
@@ -327,7 +411,9 @@ impl Thread {
                         };
                     }
                 }
-                Repetition::Draw => return Value::DRAW,
+                Repetition::Draw => {
+                    return self.draw_value[self.position.side_to_move().0 as usize];
+                }
                 Repetition::Win => return value_mate_in(get_stack(stack, 0).ply),
                 Repetition::Lose => return value_mated_in(get_stack(stack, 0).ply),
                 Repetition::Superior => {
@@ -369,8 +455,7 @@ impl Thread {
 
         // Step 4
         let excluded_move = get_stack(stack, 0).excluded_move;
-        let key =
-            self.position.key().0 ^ (u64::from(excluded_move.unwrap_unchecked().0.get()) << 16);
+        let key = self.position.key().0 ^ (excluded_move.map_or(0, |m| u64::from(m.0.get())) << 16);
         let key = Key(key);
         let (mut tte, mut tt_hit) = unsafe { (*self.tt).probe(key) };
         let mut tt_value = if tt_hit {
@@ -548,6 +633,9 @@ impl Thread {
                 && get_stack(stack, 0).static_eval.0
                     >= beta.0 - 36 * depth.0 / Depth::ONE_PLY.0 + 225
                 && excluded_move.is_none()
+                // With only pawns, passing can be much better than any move.
+                && (self.position.has_non_pawn_material(us)
+                    || self.position.hand(us).except_pawn_exist())
                 && (get_stack(stack, 0).ply >= self.null_move_pruning_min_ply
                     || us != self.null_move_pruning_color)
             {
@@ -562,6 +650,10 @@ impl Thread {
 
                 self.position.do_null_move();
                 get_stack_mut(stack, 1).static_eval_raw = get_stack(stack, 0).static_eval_raw; // key is wrong. but it's no problem.
+                #[cfg(feature = "nnue")]
+                {
+                    get_stack_mut(stack, 1).accumulator = get_stack(stack, 0).accumulator;
+                }
                 let mut null_value = -self.search::<NonPv>(
                     &mut stack[1..],
                     -beta,
@@ -616,7 +708,7 @@ impl Thread {
                     if prob_cut_count >= 2 + 2 * i32::from(cut_node) {
                         break;
                     }
-                    if m != excluded_move.unwrap_unchecked() && self.position.legal(m) {
+                    if Some(m) != excluded_move && self.position.legal(m) {
                         prob_cut_count += 1;
                         get_stack_mut(stack, 0).current_move = Some(m);
                         get_stack_mut(stack, 0).continuation_history = self
@@ -686,6 +778,17 @@ impl Thread {
             .counter_moves
             .get(prev_sq, self.position.piece_on(prev_sq));
 
+        // Counted only when a move of this node can be extended by it.
+        let mut one_reply: Option<bool> = None;
+        let recapture_sq = if self.recapture_extension
+            && !root_node
+            && self.position.captured_piece() != Piece::EMPTY
+        {
+            Some(prev_sq)
+        } else {
+            None
+        };
+
         let mut mp = MovePickerForMainSearch::new(
             &self.position,
             tt_move,
@@ -716,7 +819,7 @@ impl Thread {
         while let Some(m) = mp.next_move(&self.position, move_count_pruning) {
             debug_assert!(Some(m).is_normal_move());
 
-            if m == excluded_move.unwrap_unchecked() {
+            if Some(m) == excluded_move {
                 continue;
             }
 
@@ -778,6 +881,14 @@ impl Thread {
                         .blockers_for_king(us.inverse())
                         .is_set(m.from()))
                     || self.position.see_ge(m, Value::ZERO))
+            {
+                extension = self.check_extension;
+            }
+            if extension < Depth::ONE_PLY
+                && ((recapture_sq == Some(m.to()) && m.is_capture(&self.position))
+                    || (self.one_reply_extension
+                        && in_check
+                        && *one_reply.get_or_insert_with(|| self.position.has_single_evasion())))
             {
                 extension = Depth::ONE_PLY;
             }
@@ -795,7 +906,7 @@ impl Thread {
                     }
 
                     let lmr_depth = std::cmp::max(
-                        new_depth - reduction(improving, depth, move_count),
+                        new_depth - reduction(improving, depth, move_count, self.reduction_scale),
                         Depth::ZERO,
                     );
                     let lmr_depth = Depth(lmr_depth.0 / Depth::ONE_PLY.0);
@@ -813,7 +924,13 @@ impl Thread {
                     }
                     if lmr_depth < Depth(7)
                         && !in_check
-                        && get_stack(stack, 0).static_eval.0 + 256 + 200 * lmr_depth.0 <= alpha.0
+                        && get_stack(stack, 0).static_eval
+                            + quiet_futility_margin(
+                                lmr_depth.0,
+                                self.futility_margin_base,
+                                self.futility_margin_per_depth,
+                            )
+                            <= alpha
                     {
                         continue;
                     }
@@ -826,7 +943,14 @@ impl Thread {
                 } else if (!gives_check || extension == Depth::ZERO)
                     && !self.position.see_ge(
                         m,
-                        Value(-piece_type_value(PieceType::PAWN).0 * (depth.0 / Depth::ONE_PLY.0)),
+                        Value(
+                            -self
+                                .position
+                                .piece_values()
+                                .piece_type_value(PieceType::PAWN)
+                                .0
+                                * (depth.0 / Depth::ONE_PLY.0),
+                        ),
                     )
                 {
                     continue;
@@ -849,15 +973,19 @@ impl Thread {
             get_stack_mut(stack, 1).static_eval_raw.set_not_evaluated();
 
             // Step 16
-            let do_full_depth_search = if depth.0 >= 3 * Depth::ONE_PLY.0
+            let do_full_depth_search = if self.late_move_reduction
+                && depth.0 >= 3 * Depth::ONE_PLY.0
                 && move_count > 1 + if root_node { 3 } else { 0 }
                 && (!is_capture_or_pawn_promotion
                     || move_count_pruning
                     || get_stack(stack, 0).static_eval
-                        + capture_piece_value(self.position.captured_piece())
+                        + self
+                            .position
+                            .piece_values()
+                            .capture_piece_value(self.position.captured_piece())
                         <= alpha)
             {
-                let mut r = reduction(improving, depth, move_count);
+                let mut r = reduction(improving, depth, move_count, self.reduction_scale);
 
                 r -= Depth(singular_lmr * Depth::ONE_PLY.0);
 
@@ -979,7 +1107,13 @@ impl Thread {
             if !best_move.is_capture_or_pawn_promotion(&self.position) {
                 let bonus = stat_bonus(
                     depth
-                        + if best_value > beta + piece_type_value(PieceType::PAWN) {
+                        + if best_value
+                            > beta
+                                + self
+                                    .position
+                                    .piece_values()
+                                    .piece_type_value(PieceType::PAWN)
+                        {
                             Depth::ONE_PLY
                         } else {
                             Depth::ZERO
@@ -1042,6 +1176,19 @@ impl Thread {
 
         best_value
     }
+    // Quiescence search of self.position from the root. Used by search::qsearch().
+    fn qsearch_at_root(&mut self, alpha: Value, beta: Value) -> Value {
+        let mut stack = [Stack::new(); MAX_PLY as usize + 10];
+        for item in stack.iter_mut().take(CURRENT_STACK_INDEX) {
+            item.continuation_history = self.continuation_history.sentinel();
+        }
+        evaluate_at_root(&self.position, &mut stack);
+        if alpha + Value(1) == beta {
+            self.qsearch::<NonPv>(&mut stack, alpha, beta, Depth::ZERO)
+        } else {
+            self.qsearch::<Pv>(&mut stack, alpha, beta, Depth::ZERO)
+        }
+    }
     fn qsearch<IsPv: Bool>(
         &mut self,
         stack: &mut [Stack],
@@ -1064,12 +1211,19 @@ impl Thread {
         let in_check = self.position.in_check();
         let mut move_count = 0;
 
+        if pv_node && self.sel_depth < get_stack(stack, 0).ply + 1 {
+            self.sel_depth = get_stack(stack, 0).ply + 1;
+        }
+
         // We don't have to check repetition.
         // Because qsearch use only capture-moves, promotion-moves, and evasion-moves.
         // Their moves don't reach repetition positions.
         if get_stack_mut(stack, 0).ply >= MAX_PLY {
             return Value::DRAW;
         }
+        if self.is_max_moves_draw() {
+            return self.draw_value[self.position.side_to_move().0 as usize];
+        }
 
         debug_assert!(0 <= get_stack(stack, 0).ply && get_stack(stack, 0).ply < MAX_PLY);
 
@@ -1184,9 +1338,14 @@ impl Thread {
             move_count += 1;
             if !in_check && !gives_check && futility_base > -Value::KNOWN_WIN {
                 let futility_value = futility_base
-                    + capture_piece_value(self.position.piece_on(m.to()))
+                    + self
+                        .position
+                        .piece_values()
+                        .capture_piece_value(self.position.piece_on(m.to()))
                     + if m.is_promotion() {
-                        promote_piece_type_value(PieceType::new(m.piece_moved_before_move()))
+                        self.position
+                            .piece_values()
+                            .promote_piece_type_value(PieceType::new(m.piece_moved_before_move()))
                     } else {
                         Value::ZERO
                     };
@@ -1299,12 +1458,16 @@ impl Thread {
             return;
         }
 
-        let elapsed = self.limits.start_time.unwrap().elapsed();
+        let (elapsed, should_stop) = {
+            let timeman = self.timeman.lock().unwrap();
+            let elapsed = timeman.elapsed();
+            (elapsed, timeman.should_stop(elapsed))
+        };
 
         if (self.limits.use_time_management()
-            && (elapsed.as_millis() as i64 > self.timeman.lock().unwrap().maximum_millis() - 10
-                || self.stop_on_ponderhit.load(Ordering::Relaxed)))
-            || (self.limits.movetime.is_some() && elapsed >= self.limits.movetime.unwrap())
+            && (should_stop || self.stop_on_ponderhit.load(Ordering::Relaxed)))
+            || (self.limits.movetime.is_some()
+                && elapsed >= self.limits.movetime.unwrap().as_millis() as i64)
             || (self.limits.nodes.is_some()
                 && self.nodes_searched() >= self.limits.nodes.unwrap() as i64)
         {
@@ -1351,7 +1514,7 @@ impl Thread {
             );
         }
     }
-    fn pv_info_to_usi_string(
+    fn pv_infos(
         &self,
         nodes_searched: i64,
         multi_pv: usize,
@@ -1359,8 +1522,9 @@ impl Thread {
         alpha: Value,
         beta: Value,
         reverse: bool, // for Shogidokoro Graph
-    ) -> String {
-        let mut lines = vec![];
+    ) -> Vec<SearchInfo> {
+        let mut infos = vec![];
+        let hashfull = unsafe { (*self.tt).hashfull() };
         let elapsed_millis = self.limits.start_time.unwrap().elapsed().as_millis() as i64 + 1; // "+ 1": avoid dividing by 0
         let mut info_with_multi_pv_index = |i: usize| {
             let updated = i <= self.pv_idx && self.root_moves[i].score != -Value::INFINITE;
@@ -1372,23 +1536,24 @@ impl Thread {
             } else {
                 (depth - Depth::ONE_PLY, self.root_moves[i].previous_score)
             };
-            let line = format!(
-                "info depth {depth} seldepth {seldepth} multipv {multipv} score {score} {bound}nodes {nodes} nps {nps} time {time} pv {pv}",
-                depth = d.0 / Depth::ONE_PLY.0,
-                seldepth = self.root_moves[i].sel_depth,
-                multipv = i + 1,
-                score = v.to_usi(),
-                bound = if v >= beta {
-                    "lowerbound "
+            infos.push(SearchInfo {
+                depth: d.0 / Depth::ONE_PLY.0,
+                seldepth: self.root_moves[i].sel_depth,
+                multipv: i + 1,
+                score: v,
+                bound: if v >= beta {
+                    Bound::LOWER
                 } else if v <= alpha {
-                    "upperbound "
-                } else {""},
-                nodes = nodes_searched,
-                nps = nodes_searched * 1000 / elapsed_millis,
-                time = elapsed_millis,
-                pv = self.root_moves[i].pv.iter().map(|m| m.to_usi_string()).collect::<Vec<_>>().join(" ")
-            );
-            lines.push(line);
+                    Bound::UPPER
+                } else {
+                    Bound::EXACT
+                },
+                nodes: nodes_searched,
+                nps: nodes_searched * 1000 / elapsed_millis,
+                hashfull,
+                time_ms: elapsed_millis,
+                pv: self.root_moves[i].pv.clone(),
+            });
         };
         if reverse {
             for i in (0..multi_pv).rev() {
@@ -1399,7 +1564,39 @@ impl Thread {
                 info_with_multi_pv_index(i);
             }
         }
-        lines.join("\n")
+        infos
+    }
+    // Print PV info, or send it to search_with_callback().
+    fn send_pv_info(
+        &self,
+        nodes_searched: i64,
+        multi_pv: usize,
+        depth: Depth,
+        alpha: Value,
+        beta: Value,
+        reverse: bool,
+    ) {
+        let infos = self.pv_infos(nodes_searched, multi_pv, depth, alpha, beta, reverse);
+        match &self.info_sender {
+            Some(sender) => {
+                for info in infos {
+                    // The receiver may be gone. Then there is nothing to do.
+                    let _ = sender.send(info);
+                }
+            }
+            None => {
+                if !infos.is_empty() {
+                    println!(
+                        "{}",
+                        infos
+                            .iter()
+                            .map(|x| x.to_usi_string())
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    );
+                }
+            }
+        }
     }
 }
 
@@ -1416,6 +1613,9 @@ impl ThreadPool {
             stop: Arc::new(AtomicBool::new(false)),
             limits: LimitsType::new(),
             last_best_pv: Arc::new(Mutex::new(vec![])),
+            last_sel_depth: Arc::new(Mutex::new(0)),
+            random: Arc::new(Mutex::new(None)),
+            info_sender: None,
             handle: None,
         }
     }
@@ -1424,6 +1624,12 @@ impl ThreadPool {
             th.lock().unwrap().clear();
         }
         self.last_best_pv.lock().unwrap().clear();
+        *self.last_sel_depth.lock().unwrap() = 0;
+        *self.random.lock().unwrap() = None;
+
+        for nodes in self.nodess.iter() {
+            nodes.store(0, Ordering::Relaxed);
+        }
 
         let thread_pool_base = self.thread_pool_base.lock().unwrap();
         let mut main_thread = thread_pool_base.threads[0].lock().unwrap();
@@ -1461,6 +1667,15 @@ impl ThreadPool {
                     capture_history: CapturePieceToHistory::new(),
                     continuation_history: ContinuationHistory::new(),
                     limits: self.limits.clone(),
+                    check_extension: Depth::ONE_PLY,
+                    recapture_extension: false,
+                    one_reply_extension: false,
+                    late_move_reduction: true,
+                    reduction_scale: 100,
+                    futility_margin_base: 256,
+                    futility_margin_per_depth: 200,
+                    draw_value: [Value::DRAW; Color::NUM],
+                    max_moves_to_draw: 0,
                     tt,
                     timeman: self.timeman.clone(),
                     ehash,
@@ -1475,6 +1690,7 @@ impl ThreadPool {
                     ponder: self.ponder.clone(),
                     stop: self.stop.clone(),
                     nodess: vec![],
+                    info_sender: None,
                 }))
             })
             .collect();
@@ -1497,10 +1713,12 @@ impl ThreadPool {
         self.stop.store(false, Ordering::Relaxed);
         self.stop_on_ponderhit.store(false, Ordering::Relaxed);
         self.ponder.store(ponder_mode, Ordering::Relaxed);
-        self.timeman
-            .lock()
-            .unwrap()
-            .init(usi_options, &mut limits, pos.side_to_move(), pos.ply());
+        self.timeman.lock().unwrap().init_with_options(
+            usi_options,
+            &mut limits,
+            pos.side_to_move(),
+            pos.ply(),
+        );
         tt.new_search();
         self.limits = limits.clone();
         let root_moves = {
@@ -1513,12 +1731,15 @@ impl ThreadPool {
             root_moves
         };
         if root_moves.is_empty() {
-            println!("bestmove resign");
+            if self.info_sender.is_none() {
+                println!("bestmove resign");
+            }
             *self.last_best_pv.lock().unwrap() = vec![Move::RESIGN];
+            *self.last_sel_depth.lock().unwrap() = 0;
             return;
         }
         let dummy_nodes = Arc::new(AtomicI64::new(0)); // This isn't used.
-        let pos = Position::new_from_position(pos, dummy_nodes);
+        let mut pos = Position::new_from_position(pos, dummy_nodes);
         let nodess_cloned = self.nodess.clone();
         let timeman_cloned = self.timeman.clone();
         let previous_score_cloned = self.previous_score.clone();
@@ -1527,6 +1748,9 @@ impl ThreadPool {
         let ponder_cloned = self.ponder.clone();
         let usi_options_cloned = usi_options.clone();
         let last_best_pv_cloned = self.last_best_pv.clone();
+        let last_sel_depth_cloned = self.last_sel_depth.clone();
+        let random_cloned = self.random.clone();
+        let info_sender_cloned = self.info_sender.clone();
         self.handle = Some(std::thread::spawn(move || {
             let mut v = vec![];
             for (i, thread) in thread_pool_base_cloned
@@ -1546,6 +1770,7 @@ impl ThreadPool {
                 let limits_cloned = limits.clone();
                 let usi_options_cloned = usi_options_cloned.clone();
                 let timeman_cloned = timeman_cloned.clone();
+                let info_sender_cloned = info_sender_cloned.clone();
                 let worker = move || {
                     let mut th = thread_cloned.lock().unwrap();
                     th.best_move_changes.store(0, Ordering::Relaxed);
@@ -1556,6 +1781,7 @@ impl ThreadPool {
                     th.position = pos;
                     th.usi_options = usi_options_cloned;
                     th.timeman = timeman_cloned;
+                    th.info_sender = info_sender_cloned;
                     th.iterative_deepening_loop();
                 };
                 if i == 0 {
@@ -1619,6 +1845,25 @@ impl ThreadPool {
                 thread_pool_base_cloned.lock().unwrap().threads[0].clone()
             };
 
+            let seed = usi_options_cloned.get_i64("Random_Seed");
+            if seed != 0 {
+                // Choose one of the PV lines which have the same score as the best move.
+                let mut th = best_thread.lock().unwrap();
+                let best_score = th.root_moves[0].score;
+                let candidates_num = th
+                    .root_moves
+                    .iter()
+                    .take(multi_pv)
+                    .take_while(|x| x.score == best_score)
+                    .count();
+                let mut random = random_cloned.lock().unwrap();
+                if random.as_ref().is_none_or(|(s, _)| *s != seed) {
+                    *random = Some((seed, SeedableRng::seed_from_u64(seed as u64)));
+                }
+                let i = random.as_mut().unwrap().1.gen_range(0, candidates_num);
+                th.root_moves.swap(0, i);
+            }
+
             *previous_score_cloned.lock().unwrap() =
                 best_thread.lock().unwrap().root_moves[0].score;
 
@@ -1626,44 +1871,141 @@ impl ThreadPool {
                 .lock()
                 .unwrap()
                 .nodes_searched();
-            if let Ok(best_thread) = best_thread.lock() {
+            if let Ok(mut best_thread) = best_thread.lock() {
                 // Always send again PV info.
-                println!(
-                    "{}",
-                    best_thread.pv_info_to_usi_string(
-                        nodes_searched,
-                        multi_pv,
-                        best_thread.completed_depth,
-                        -Value::INFINITE,
-                        Value::INFINITE,
-                        true,
-                    )
+                best_thread.send_pv_info(
+                    nodes_searched,
+                    multi_pv,
+                    best_thread.completed_depth,
+                    -Value::INFINITE,
+                    Value::INFINITE,
+                    true,
                 );
                 let mut s = format!(
                     "bestmove {}",
                     best_thread.root_moves[0].pv[0].to_usi_string(),
                 );
                 if usi_options_cloned.get_bool("USI_Ponder")
-                    && best_thread.root_moves[0].pv.len() >= 2
+                    && (best_thread.root_moves[0].pv.len() >= 2 || {
+                        let tt = best_thread.tt;
+                        best_thread.root_moves[0].extract_ponder_from_tt(&mut pos, tt)
+                    })
                 {
                     s += &format!(
                         " ponder {}",
                         best_thread.root_moves[0].pv[1].to_usi_string()
                     );
                 }
-                println!("{}", s);
+                // With info_sender, the caller receives the best move as the result instead.
+                if info_sender_cloned.is_none() {
+                    println!("{}", s);
+                }
+            }
+            // Drop all the senders, so that the receiver knows the end of the search.
+            for th in thread_pool_base_cloned.lock().unwrap().threads.iter() {
+                th.lock().unwrap().info_sender = None;
             }
-            *last_best_pv_cloned.lock().unwrap() =
-                best_thread.lock().unwrap().root_moves[0].pv.clone();
+            drop(info_sender_cloned);
+            let best_thread = best_thread.lock().unwrap();
+            *last_best_pv_cloned.lock().unwrap() = best_thread.root_moves[0].pv.clone();
+            *last_sel_depth_cloned.lock().unwrap() = best_thread.root_moves[0].sel_depth;
         }));
     }
+    // Search until "limits" or "stop" stops it, and return the best move found so far.
+    #[allow(dead_code)]
+    pub fn search_with_stop(
+        &mut self,
+        pos: &Position,
+        tt: &mut TranspositionTable,
+        limits: LimitsType,
+        usi_options: &UsiOptions,
+        stop: Arc<AtomicBool>,
+    ) -> SearchResult {
+        let start_time = std::time::Instant::now();
+        self.wait_for_search_finished();
+        let ponder_mode = false;
+        self.start_thinking(pos, tt, limits, usi_options, ponder_mode);
+        // The pool keeps its own flag. "stop" is forwarded to it until the search finishes,
+        // so a "stop" which is already set before the search also stops it.
+        while !self.handle.as_ref().is_none_or(|x| x.is_finished()) {
+            if stop.load(Ordering::Relaxed) {
+                self.stop.store(true, Ordering::Relaxed);
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        self.wait_for_search_finished();
+        self.search_result(start_time)
+    }
+    // Same as search_with_stop(), but PV info is passed to on_info instead of being printed,
+    // and "bestmove" isn't printed.
+    // on_info is called on the caller's thread.
+    #[allow(dead_code)]
+    pub fn search_with_callback(
+        &mut self,
+        pos: &Position,
+        tt: &mut TranspositionTable,
+        limits: LimitsType,
+        usi_options: &UsiOptions,
+        mut on_info: impl FnMut(&SearchInfo),
+    ) -> SearchResult {
+        let start_time = std::time::Instant::now();
+        self.wait_for_search_finished();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        self.info_sender = Some(sender);
+        let ponder_mode = false;
+        self.start_thinking(pos, tt, limits, usi_options, ponder_mode);
+        self.info_sender = None;
+        // recv() fails when the search has dropped all the senders.
+        while let Ok(info) = receiver.recv() {
+            on_info(&info);
+        }
+        self.wait_for_search_finished();
+        self.search_result(start_time)
+    }
+    fn search_result(&self, start_time: std::time::Instant) -> SearchResult {
+        let pv = self.last_best_pv.lock().unwrap();
+        let time_ms = start_time.elapsed().as_millis() as i64 + 1; // "+ 1": avoid dividing by 0
+        SearchResult {
+            best_move: pv[0],
+            ponder_move: pv.get(1).cloned(),
+            sel_depth: *self.last_sel_depth.lock().unwrap(),
+            time_ms,
+            nps: self.nodes_searched() * 1000 / time_ms,
+        }
+    }
+    pub fn ponderhit(&mut self) {
+        self.timeman.lock().unwrap().ponderhit();
+        self.ponder.store(false, Ordering::Relaxed);
+    }
+    #[cfg(test)]
+    pub fn histories_are_cleared(&self) -> bool {
+        self.thread_pool_base
+            .lock()
+            .unwrap()
+            .threads
+            .iter()
+            .all(|th| {
+                let th = th.lock().unwrap();
+                th.main_history.is_cleared()
+                    && th.capture_history.is_cleared()
+                    && th.counter_moves.is_cleared()
+            })
+    }
+    // Quiescence search of "pos" by the main thread without starting the search.
+    pub fn qsearch(&mut self, pos: &Position, alpha: Value, beta: Value) -> Value {
+        self.wait_for_search_finished();
+        let thread = self.thread_pool_base.lock().unwrap().threads[0].clone();
+        let mut th = thread.lock().unwrap();
+        th.position = Position::new_from_position(pos, th.nodes.clone());
+        th.qsearch_at_root(alpha, beta)
+    }
     pub fn wait_for_search_finished(&mut self) {
         if let Some(handle) = self.handle.take() {
             handle.join().unwrap();
         }
     }
     #[allow(dead_code)]
-    fn nodes_searched(&self) -> i64 {
+    pub fn nodes_searched(&self) -> i64 {
         self.nodess
             .iter()
             .fold(0, |sum, nodes| sum + nodes.load(Ordering::Relaxed))
@@ -1676,6 +2018,83 @@ impl Drop for ThreadPool {
     }
 }
 
+// What a search test needs. The evaluation files are loaded and the hash tables are small.
+#[cfg(test)]
+pub struct SearchTestContext {
+    pub thread_pool: ThreadPool,
+    pub usi_options: UsiOptions,
+    pub tt: TranspositionTable,
+    pub ehash: EvalHash,
+}
+
+#[cfg(test)]
+impl SearchTestContext {
+    pub fn set_threads(&mut self, threads: usize) {
+        self.thread_pool.set(threads, &mut self.tt, &mut self.ehash);
+    }
+    pub fn set_option(&mut self, name: &str, value: &str) {
+        self.usi_options.set_and_apply(
+            name,
+            value,
+            &mut self.thread_pool,
+            &mut self.tt,
+            &mut self.ehash,
+        );
+    }
+    // The hash tables and the histories are cleared, so the next search doesn't depend on the previous ones.
+    pub fn clear(&mut self) {
+        self.tt.clear();
+        self.ehash.clear();
+        self.thread_pool.clear();
+    }
+    pub fn search(&mut self, pos: &Position, limits: LimitsType) -> SearchResult {
+        let stop = Arc::new(AtomicBool::new(false));
+        self.thread_pool
+            .search_with_stop(pos, &mut self.tt, limits, &self.usi_options, stop)
+    }
+    pub fn search_with_callback(
+        &mut self,
+        pos: &Position,
+        limits: LimitsType,
+        on_info: impl FnMut(&SearchInfo),
+    ) -> SearchResult {
+        self.thread_pool
+            .search_with_callback(pos, &mut self.tt, limits, &self.usi_options, on_info)
+    }
+    // The root moves of the main thread after the search.
+    pub fn root_moves(&self) -> RootMoves {
+        let thread_pool_base = self.thread_pool.thread_pool_base.lock().unwrap();
+        let main_thread = thread_pool_base.threads[0].lock().unwrap();
+        main_thread.root_moves.clone()
+    }
+}
+
+// Runs f on a thread whose stack is large enough for the search.
+#[cfg(test)]
+pub fn run_search_test(f: impl FnOnce(SearchTestContext) + Send + 'static) {
+    const STACK_SIZE: usize = 128 * 1024 * 1024;
+    std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(|| {
+            let mut thread_pool = ThreadPool::new();
+            let usi_options = UsiOptions::new();
+            let mut tt = TranspositionTable::new();
+            let mut ehash = EvalHash::new();
+            tt.resize(16, &mut thread_pool);
+            ehash.resize(16, &mut thread_pool);
+            load_evaluate_files(&usi_options.get_string("Eval_Dir")).unwrap();
+            f(SearchTestContext {
+                thread_pool,
+                usi_options,
+                tt,
+                ehash,
+            });
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
 #[test]
 fn test_start_thinking() {
     const STACK_SIZE: usize = 128 * 1024 * 1024;
@@ -1710,3 +2129,389 @@ fn test_start_thinking() {
         .join()
         .unwrap();
 }
+
+#[test]
+fn test_ponderhit() {
+    run_search_test(|mut ctx| {
+        ctx.set_option("USI_Ponder", "true");
+        let limits = {
+            let mut limits = LimitsType::new();
+            limits.movetime = Some(std::time::Duration::from_millis(100));
+            limits.start_time = Some(std::time::Instant::now());
+            limits
+        };
+        ctx.set_threads(1);
+        let pos = Position::new();
+        let ponder_mode = true;
+        ctx.thread_pool
+            .start_thinking(&pos, &mut ctx.tt, limits, &ctx.usi_options, ponder_mode);
+        // pondering never ends by itself, even if it exceeds movetime.
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        assert!(ctx.thread_pool.last_best_pv.lock().unwrap().is_empty());
+        ctx.thread_pool.ponderhit();
+        ctx.thread_pool.wait_for_search_finished();
+        let pv = ctx.thread_pool.last_best_pv.lock().unwrap().clone();
+        assert!(!pv.is_empty());
+        let mut mlist = MoveList::new();
+        mlist.generate::<LegalType>(&pos, 0);
+        assert!(mlist.slice(0).iter().any(|x| x.mv == pv[0]));
+    });
+}
+
+#[test]
+fn test_lazy_smp_nodes() {
+    run_search_test(|mut ctx| {
+        let mut search = |threads: usize| {
+            ctx.set_threads(threads);
+            ctx.clear();
+            ctx.search(&Position::new(), LimitsType::with_depth(12));
+            assert!(!ctx.thread_pool.last_best_pv.lock().unwrap().is_empty());
+            let main_thread_nodes = ctx.thread_pool.nodess[0].load(Ordering::Relaxed);
+            (ctx.thread_pool.nodes_searched(), main_thread_nodes)
+        };
+        // The shared TT may make the main thread faster, so the totals of the two runs aren't
+        // compared. The helper thread adds its nodes to the main thread's ones.
+        let (single_thread_nodes, main_thread_nodes) = search(1);
+        assert_eq!(single_thread_nodes, main_thread_nodes);
+        let (two_threads_nodes, main_thread_nodes) = search(2);
+        assert!(main_thread_nodes < two_threads_nodes);
+    });
+}
+
+#[test]
+fn test_search_with_stop() {
+    run_search_test(|mut ctx| {
+        ctx.set_threads(2);
+        let limits = {
+            let mut limits = LimitsType::new();
+            limits.infinite = Some(());
+            limits.start_time = Some(std::time::Instant::now());
+            limits
+        };
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_cloned = stop.clone();
+        let stopper = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            stop_cloned.store(true, Ordering::Relaxed);
+        });
+        let start_time = std::time::Instant::now();
+        let pos = Position::new();
+        let result = ctx.thread_pool.search_with_stop(
+            &pos,
+            &mut ctx.tt,
+            limits.clone(),
+            &ctx.usi_options,
+            stop.clone(),
+        );
+        assert!(start_time.elapsed() < std::time::Duration::from_secs(5));
+        stopper.join().unwrap();
+        let mut mlist = MoveList::new();
+        mlist.generate::<LegalType>(&pos, 0);
+        assert!(mlist.slice(0).iter().any(|x| x.mv == result.best_move));
+        // The pool's own flag isn't replaced by the caller's one.
+        assert!(!Arc::ptr_eq(&ctx.thread_pool.stop, &stop));
+
+        // A "stop" which is already set stops the search too.
+        let start_time = std::time::Instant::now();
+        let result =
+            ctx.thread_pool
+                .search_with_stop(&pos, &mut ctx.tt, limits, &ctx.usi_options, stop);
+        assert!(start_time.elapsed() < std::time::Duration::from_secs(5));
+        assert!(mlist.slice(0).iter().any(|x| x.mv == result.best_move));
+    });
+}
+
+#[test]
+fn test_search_with_callback() {
+    run_search_test(|mut ctx| {
+        // The helper threads hold the senders too.
+        ctx.set_threads(2);
+        let pos = Position::new();
+        // Returns only after all the senders are dropped, so it can be called repeatedly.
+        for _ in 0..2 {
+            let mut infos = vec![];
+            let result = ctx.search_with_callback(&pos, LimitsType::with_depth(4), |info| {
+                infos.push((info.depth, info.pv.clone(), info.to_usi_string()));
+            });
+            assert!(!infos.is_empty());
+            assert!(infos.iter().all(|(_, pv, _)| !pv.is_empty()));
+            assert!(infos.iter().all(|(_, _, s)| s.starts_with("info depth ")));
+            assert!(infos.iter().all(|(_, _, s)| s.contains(" hashfull ")));
+            assert_eq!(infos.iter().map(|(depth, _, _)| *depth).max(), Some(4));
+            // The last info is the final PV.
+            assert_eq!(infos.last().unwrap().1[0], result.best_move);
+        }
+    });
+}
+
+#[test]
+fn test_check_extension() {
+    run_search_test(|mut ctx| {
+        ctx.set_threads(1);
+        let mut search = |sfen: &str, depth: u32, check_extension_plies: &str| {
+            ctx.set_option("Check_Extension_Plies", check_extension_plies);
+            ctx.clear();
+            let pos = Position::new_from_sfen(sfen).unwrap();
+            let result = ctx.search(&pos, LimitsType::with_depth(depth));
+            (
+                result.best_move.to_usi_string(),
+                ctx.root_moves()[0].sel_depth,
+                ctx.thread_pool.nodes_searched(),
+            )
+        };
+        // G*5b is mate.
+        let (best_move, sel_depth, _) = search("4k4/9/4P4/9/9/9/9/9/4K4 b G 1", 1, "1");
+        assert_eq!(best_move, "G*5b");
+        // the checking move is searched deeper than the nominal depth.
+        assert!(sel_depth > 1);
+
+        // The quiescence search may reach the same ply, so the nodes are compared too.
+        let sfen = "4k4/9/9/9/9/9/9/9/4K4 b RG 1";
+        let (_, sel_depth, nodes) = search(sfen, 2, "1");
+        let (_, sel_depth_without_extension, nodes_without_extension) = search(sfen, 2, "0");
+        assert!(sel_depth_without_extension < sel_depth || nodes_without_extension < nodes);
+    });
+}
+
+#[test]
+fn test_search_result_sel_depth() {
+    run_search_test(|mut ctx| {
+        ctx.set_threads(1);
+        let depth = 3;
+        let result = ctx.search(&Position::new(), LimitsType::with_depth(depth));
+        // The leaves of the PV are searched by the quiescence search.
+        assert!(result.sel_depth > depth as i32);
+    });
+}
+
+#[test]
+fn test_late_move_reduction() {
+    run_search_test(|mut ctx| {
+        ctx.set_threads(1);
+        // 8h4d takes the rook.
+        let pos = Position::new_from_sfen(
+            "lnsgkgsnl/7b1/ppppppppp/5r3/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL b - 1",
+        )
+        .unwrap();
+        let mut search = |late_move_reduction: &str, scale: &str| {
+            ctx.set_option("Late_Move_Reduction", late_move_reduction);
+            ctx.set_option("Late_Move_Reduction_Scale", scale);
+            ctx.clear();
+            let result = ctx.search(&pos, LimitsType::with_depth(7));
+            (result.best_move, ctx.thread_pool.nodes_searched())
+        };
+        let expected = Move::new_from_usi_str("8h4d", &pos).unwrap();
+        let (best_move_reduced, nodes_reduced) = search("true", "100");
+        let (best_move_full, nodes_full) = search("false", "100");
+        let (best_move_scaled, _) = search("true", "200");
+        assert_eq!(best_move_reduced, expected);
+        assert_eq!(best_move_full, expected);
+        assert_eq!(best_move_scaled, expected);
+        assert!(nodes_reduced < nodes_full);
+    });
+}
+
+#[test]
+fn test_futility_margin() {
+    run_search_test(|mut ctx| {
+        ctx.set_threads(1);
+        // R*3b 1a2a G*2b is mate.
+        let pos = Position::new_from_sfen("8k/9/9/9/9/9/9/9/K8 b RG 1").unwrap();
+        let mut search = |base: &str, per_depth: &str| {
+            ctx.set_option("Futility_Margin_Base", base);
+            ctx.set_option("Futility_Margin_Per_Depth", per_depth);
+            ctx.clear();
+            let mut score = Value::NONE;
+            ctx.search_with_callback(&pos, LimitsType::with_depth(7), |info| {
+                score = info.score;
+            });
+            score
+        };
+        let score = search("256", "200");
+        assert_eq!(score.to_usi(), "mate 3");
+        // Quiet moves are pruned as much as possible, but the checks aren't.
+        assert_eq!(search("0", "0"), score);
+    });
+}
+
+#[test]
+fn test_null_move_pruning() {
+    run_search_test(|mut ctx| {
+        ctx.set_threads(1);
+        // N*2d 2a2c G*1b is mate. Black has no non-pawn piece on the board, and White has only a lance.
+        let pos = Position::new_from_sfen("7lk/9/7P1/9/9/9/9/9/K8 b GN 1").unwrap();
+        assert!(!pos.has_non_pawn_material(Color::BLACK));
+        assert!(pos.has_non_pawn_material(Color::WHITE));
+        let mut score = Value::NONE;
+        let result = ctx.search_with_callback(&pos, LimitsType::with_depth(9), |info| {
+            score = info.score;
+        });
+        assert_eq!(score.to_usi(), "mate 3");
+        assert_eq!(
+            result.best_move,
+            Move::new_from_usi_str("N*2d", &pos).unwrap()
+        );
+    });
+}
+
+#[test]
+fn test_search_result_nps() {
+    run_search_test(|mut ctx| {
+        ctx.set_threads(1);
+        let result = ctx.search(&Position::new(), LimitsType::with_depth(5));
+        let nodes = ctx.thread_pool.nodes_searched();
+        assert!(nodes > 0);
+        assert!(result.time_ms > 0);
+        assert_eq!(result.nps, nodes * 1000 / result.time_ms);
+    });
+}
+
+#[test]
+fn test_extract_pv() {
+    run_search_test(|mut ctx| {
+        ctx.set_threads(1);
+        let mut pos = Position::new();
+        let result = ctx.search(&pos, LimitsType::with_depth(4));
+        let sfen = pos.to_sfen();
+        let pv = extract_pv(&mut pos, &ctx.tt, 3);
+        assert!(!pv.is_empty() && pv.len() <= 3);
+        assert_eq!(pv[0], result.best_move);
+        assert_eq!(pos.to_sfen(), sfen);
+        assert!(extract_pv(&mut pos, &ctx.tt, 0).is_empty());
+    });
+}
+
+#[test]
+fn test_aspiration_window() {
+    run_search_test(|mut ctx| {
+        ctx.set_threads(1);
+        // 5g5f takes the rook.
+        let pos = Position::new_from_sfen(
+            "lnsgkgsnl/7b1/ppppppppp/9/9/4r4/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1",
+        )
+        .unwrap();
+        let mut search = |aspiration_delta: &str| {
+            ctx.set_option("Aspiration_Delta", aspiration_delta);
+            ctx.clear();
+            ctx.search(&pos, LimitsType::with_depth(6)).best_move
+        };
+        // "0" is the full window search.
+        let best_move = Move::new_from_usi_str("5g5f", &pos).unwrap();
+        assert_eq!(search("20"), best_move);
+        assert_eq!(search("0"), best_move);
+    });
+}
+
+#[test]
+fn test_random_seed() {
+    run_search_test(|mut ctx| {
+        ctx.set_threads(1);
+        ctx.set_option("MultiPV", "4");
+        let pos = Position::new();
+        // Plays "n" searches as a new game, and returns the best moves.
+        let mut play_game = |seed: &str, n: usize| {
+            ctx.set_option("Random_Seed", seed);
+            ctx.thread_pool.clear();
+            (0..n)
+                .map(|_| {
+                    ctx.tt.clear();
+                    ctx.ehash.clear();
+                    let best_move = ctx.search(&pos, LimitsType::with_depth(2)).best_move;
+                    let root_moves = ctx.root_moves();
+                    // best_move is one of the searched PV lines which have the best score.
+                    assert!(root_moves
+                        .iter()
+                        .take(4)
+                        .any(|x| x.pv[0] == best_move && x.score == root_moves[0].score));
+                    best_move
+                })
+                .collect::<Vec<_>>()
+        };
+        // The same seed plays the same moves in each game.
+        let moves = play_game("12345", 8);
+        assert_eq!(moves, play_game("12345", 8));
+    });
+}
+
+#[test]
+fn test_contempt() {
+    run_search_test(|mut ctx| {
+        ctx.set_threads(1);
+        // White is a pawn down, and 4a5a repeats the first position.
+        let mut pos = Position::new_from_sfen("4k4/3g1g3/9/9/9/9/4P4/3G1G3/4K4 b - 1").unwrap();
+        for move_str in &["5i4i", "5a4a", "4i5i"] {
+            let m = Move::new_from_usi_str(move_str, &pos).unwrap();
+            let gives_check = pos.gives_check(m);
+            pos.do_move(m, gives_check);
+        }
+        let repetition_move = Move::new_from_usi_str("4a5a", &pos).unwrap();
+        let mut search = |contempt: &str| {
+            ctx.set_option("Contempt", contempt);
+            ctx.clear();
+            ctx.search(&pos, LimitsType::with_depth(3)).best_move
+        };
+        assert_eq!(search("0"), repetition_move);
+        assert_ne!(search("1000"), repetition_move);
+    });
+}
+
+#[test]
+fn test_perpetual_check() {
+    run_search_test(|mut ctx| {
+        ctx.set_option("MultiPV", "500");
+        ctx.set_threads(1);
+        // Black is behind, and has checked continuously.
+        let mut pos = Position::new_from_sfen("8k/9/9/9/9/9/9/9/4K2R1 b GSgs 1").unwrap();
+        for move_str in &["2i1i", "1a2a", "1i2i", "2a1a"] {
+            let m = Move::new_from_usi_str(move_str, &pos).unwrap();
+            let gives_check = pos.gives_check(m);
+            pos.do_move(m, gives_check);
+        }
+        let perpetual_check_move = Move::new_from_usi_str("2i1i", &pos).unwrap();
+        assert!(pos.gives_check(perpetual_check_move));
+        let result = ctx.search(&pos, LimitsType::with_depth(3));
+        assert_ne!(result.best_move, perpetual_check_move);
+        let root_moves = ctx.root_moves();
+        let rm = root_moves
+            .iter()
+            .find(|x| x.pv[0] == perpetual_check_move)
+            .unwrap();
+        // The side which checks continuously loses.
+        assert!(rm.score <= Value::MATED_IN_MAX_PLY);
+    });
+}
+
+#[test]
+fn test_max_moves_to_draw() {
+    run_search_test(|mut ctx| {
+        ctx.set_threads(1);
+        // Black is a pawn up at ply 100.
+        let pos = Position::new_from_sfen("4k4/3g1g3/9/9/9/9/4P4/3G1G3/4K4 b - 100").unwrap();
+        let mut search = |max_moves_to_draw: &str| {
+            ctx.set_option("Max_Moves_To_Draw", max_moves_to_draw);
+            ctx.clear();
+            ctx.search(&pos, LimitsType::with_depth(3));
+            ctx.root_moves()[0].score
+        };
+        assert!(search("0") > Value::DRAW);
+        assert!(search("200") > Value::DRAW);
+        // Every move reaches ply 101.
+        assert_eq!(search("101"), Value::DRAW);
+    });
+}
+
+#[test]
+fn test_evaluate_during_search() {
+    run_search_test(|mut ctx| {
+        ctx.set_threads(1);
+        // evaluate() compares every 64th call with the full evaluation, and panics on a mismatch.
+        // The captures, the promotions and the king moves are searched from here.
+        let pos = Position::new_from_sfen(
+            "l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w RGgsn5p 1",
+        )
+        .unwrap();
+        let calls = EVALUATE_CALLS.load(Ordering::Relaxed);
+        ctx.search(&pos, LimitsType::with_depth(4));
+        assert!(EVALUATE_CALLS.load(Ordering::Relaxed) >= calls + 64);
+    });
+}
@@ -70,7 +70,20 @@ impl TimeManagement {
             slow_mover,
         )
     }
-    pub fn init(&mut self, usi_optoins: &UsiOptions, limits: &mut LimitsType, us: Color, ply: i32) {
+    // The budgets for the clocks of "limits" with the default options.
+    // "byoyomi" of the "go" command is limits.movetime.
+    #[allow(dead_code)]
+    pub fn init(&mut self, limits: &LimitsType, ply: i32, us: Color) {
+        let mut limits = limits.clone();
+        self.init_with_options(&UsiOptions::new(), &mut limits, us, ply);
+    }
+    pub fn init_with_options(
+        &mut self,
+        usi_optoins: &UsiOptions,
+        limits: &mut LimitsType,
+        us: Color,
+        ply: i32,
+    ) {
         self.start_time = limits.start_time;
         let min_thinking_time = usi_optoins.get_i64("Minimum_Thinking_Time") as u64;
         self.optimum_time_milli = std::cmp::max(
@@ -118,14 +131,93 @@ impl TimeManagement {
             limits.movetime = None;
         }
     }
+    // The clock of our side starts at "ponderhit", not at "go ponder".
+    pub fn ponderhit(&mut self) {
+        self.start_time = Some(std::time::Instant::now());
+    }
     pub fn optimum_millis(&self) -> i64 {
         self.optimum_time_milli.as_millis() as i64
     }
     pub fn maximum_millis(&self) -> i64 {
         self.maximum_time_milli.as_millis() as i64
     }
+    pub fn should_stop(&self, elapsed: i64) -> bool {
+        elapsed > self.maximum_millis() - 10
+    }
     pub fn elapsed(&self) -> i64 {
         let duration = self.start_time.unwrap().elapsed();
         (duration.as_secs() * 1000 + u64::from(duration.subsec_millis())) as i64
     }
 }
+
+#[test]
+fn test_init_sudden_death() {
+    let mut timeman = TimeManagement::new();
+    let mut limits = LimitsType::new();
+    limits.time[Color::BLACK.0 as usize] = std::time::Duration::from_millis(60_000);
+    timeman.init(&limits, 1, Color::BLACK);
+    assert!(0 < timeman.optimum_millis());
+    assert!(timeman.optimum_millis() <= timeman.maximum_millis());
+    assert!(timeman.maximum_millis() < 60_000);
+    assert!(limits.use_time_management());
+}
+
+#[test]
+fn test_init_fischer() {
+    let sudden_death = {
+        let mut timeman = TimeManagement::new();
+        let mut limits = LimitsType::new();
+        limits.time[Color::WHITE.0 as usize] = std::time::Duration::from_millis(60_000);
+        timeman.init(&limits, 2, Color::WHITE);
+        timeman
+    };
+    let mut timeman = TimeManagement::new();
+    let mut limits = LimitsType::new();
+    limits.time[Color::WHITE.0 as usize] = std::time::Duration::from_millis(60_000);
+    limits.inc[Color::WHITE.0 as usize] = std::time::Duration::from_millis(10_000);
+    timeman.init(&limits, 2, Color::WHITE);
+    assert!(sudden_death.optimum_millis() < timeman.optimum_millis());
+    assert!(sudden_death.maximum_millis() < timeman.maximum_millis());
+    assert!(timeman.optimum_millis() <= timeman.maximum_millis());
+    assert!(timeman.maximum_millis() <= 60_000);
+}
+
+#[test]
+fn test_init_byoyomi() {
+    let usi_options = UsiOptions::new();
+    let min_thinking_time = usi_options.get_i64("Minimum_Thinking_Time");
+    let mut timeman = TimeManagement::new();
+    let mut limits = LimitsType::new();
+    limits.movetime = Some(std::time::Duration::from_millis(10_000));
+    timeman.init(&limits, 1, Color::BLACK);
+    assert_eq!(timeman.optimum_millis(), min_thinking_time + 10_000);
+    assert_eq!(timeman.maximum_millis(), min_thinking_time + 10_000);
+    // byoyomi only. stop by movetime.
+    timeman.init_with_options(&usi_options, &mut limits, Color::BLACK, 1);
+    assert!(limits.movetime.is_some());
+    assert!(!limits.use_time_management());
+
+    // byoyomi with main time. stop by time management.
+    let mut limits = LimitsType::new();
+    limits.time[Color::BLACK.0 as usize] = std::time::Duration::from_millis(60_000);
+    limits.movetime = Some(std::time::Duration::from_millis(10_000));
+    timeman.init_with_options(&usi_options, &mut limits, Color::BLACK, 1);
+    assert!(10_000 < timeman.optimum_millis());
+    assert!(limits.movetime.is_none());
+    assert!(limits.use_time_management());
+}
+
+#[test]
+fn test_should_stop() {
+    let usi_options = UsiOptions::new();
+    let mut timeman = TimeManagement::new();
+    let mut limits = LimitsType::new();
+    limits.start_time = Some(std::time::Instant::now());
+    limits.time[Color::BLACK.0 as usize] = std::time::Duration::from_millis(10_000);
+    timeman.init_with_options(&usi_options, &mut limits, Color::BLACK, 1);
+    let maximum = timeman.maximum_millis();
+    assert!(!timeman.should_stop(0));
+    assert!(!timeman.should_stop(maximum / 2));
+    assert!(timeman.should_stop(maximum));
+    assert!(timeman.should_stop(maximum + 1));
+}
@@ -123,6 +123,21 @@ impl TranspositionTable {
             *x = unsafe { std::mem::zeroed() };
         });
     }
+    // Zero clearing with a fixed number of threads. Each thread clears a contiguous part of self.table.
+    // "Clear_Hash" and "usinewgame" use this with the number of the search threads.
+    pub fn clear_parallel(&mut self, threads: usize) {
+        let threads = std::cmp::max(threads, 1);
+        let chunk_size = std::cmp::max(self.table.len().div_ceil(threads), 1);
+        std::thread::scope(|s| {
+            for chunk in self.table.chunks_mut(chunk_size) {
+                s.spawn(move || {
+                    for x in chunk.iter_mut() {
+                        *x = unsafe { std::mem::zeroed() };
+                    }
+                });
+            }
+        });
+    }
     pub fn new_search(&mut self) {
         self.generation8 = self.generation8.wrapping_add(8);
     }
@@ -159,9 +174,41 @@ impl TranspositionTable {
         let found = false;
         (replace, found)
     }
+    // Unlike probe(), this doesn't refresh the entry and doesn't return an entry to be replaced.
+    pub fn peek(&self, key: Key) -> Option<&TTEntry> {
+        let key16 = (key.0 >> 48) as u16;
+        if key16 == 0 {
+            return None;
+        }
+        self.table[self.cluster_index(key)]
+            .entry
+            .iter()
+            .find(|x| x.key16 == key16)
+    }
     pub fn generation(&self) -> u8 {
         self.generation8
     }
+    // Permille of the used entries in the current generation. Only the first 1000 entries are sampled.
+    pub fn hashfull(&self) -> i32 {
+        let used = self
+            .table
+            .iter()
+            .flat_map(|x| x.entry.iter())
+            .take(1000)
+            .filter(|x| x.key16 != 0 && (x.genbound8 & 0xf8) == self.generation8)
+            .count();
+        let sampled = std::cmp::min(self.table.len() * CLUSTER_SIZE, 1000);
+        if sampled == 0 {
+            return 0;
+        }
+        (used * 1000 / sampled) as i32
+    }
+    #[cfg(test)]
+    pub fn is_cleared(&self) -> bool {
+        self.table
+            .iter()
+            .all(|x| x.entry.iter().all(|e| e.key16 == 0 && e.depth8 == 0))
+    }
 }
 
 #[test]
@@ -233,3 +280,109 @@ fn test_probe() {
         .join()
         .unwrap();
 }
+
+#[test]
+fn test_clear_parallel() {
+    const STACK_SIZE: usize = 128 * 1024 * 1024;
+    std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(|| {
+            let mut thread_pool = ThreadPool::new();
+            let mut tt = TranspositionTable::new();
+            tt.resize(4, &mut thread_pool);
+            let gen8 = tt.generation8;
+            for i in 1..10000u64 {
+                let key = Key(i.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+                let (tte, _found) = tt.probe(key);
+                tte.save(
+                    key,
+                    Value(1),
+                    true,
+                    Bound::EXACT,
+                    Depth(3),
+                    None,
+                    Value(1),
+                    gen8,
+                );
+            }
+            assert!(tt
+                .table
+                .iter()
+                .any(|x| x.entry.iter().any(|e| e.key16 != 0)));
+
+            tt.clear_parallel(4);
+            assert_eq!(
+                tt.table.len(),
+                4 * 1024 * 1024 / std::mem::size_of::<TTCluster>()
+            );
+            assert!(tt.table.iter().all(|x| x.entry.iter().all(|e| e.key16 == 0
+                && e.mv16 == 0
+                && e.value16 == 0
+                && e.eval16 == 0
+                && e.genbound8 == 0
+                && e.depth8 == 0)));
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn test_hashfull() {
+    const STACK_SIZE: usize = 128 * 1024 * 1024;
+    std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(|| {
+            let mut thread_pool = ThreadPool::new();
+            let mut tt = TranspositionTable::new();
+            tt.resize(1, &mut thread_pool);
+            assert_eq!(tt.hashfull(), 0);
+            let cluster_count = tt.table.len() as u64;
+            let fill = |tt: &mut TranspositionTable, begin: u64, end: u64| {
+                let generation = tt.generation();
+                for i in begin..end {
+                    // One entry for each cluster. The upper 16 bits are key16 and mustn't be 0.
+                    let key = Key((1 << 48) | i);
+                    let (tte, _found) = tt.probe(key);
+                    tte.save(
+                        key,
+                        Value(1),
+                        false,
+                        Bound::EXACT,
+                        Depth(1),
+                        None,
+                        Value(1),
+                        generation,
+                    );
+                }
+            };
+            // 1000 entries are sampled. They are in the first 334 clusters.
+            fill(&mut tt, 0, 167);
+            assert_eq!(tt.hashfull(), 167);
+            fill(&mut tt, 167, cluster_count);
+            assert_eq!(tt.hashfull(), 334);
+            // The second entry of each cluster.
+            for i in 0..cluster_count {
+                let key = Key((2 << 48) | i);
+                let generation = tt.generation();
+                let (tte, _found) = tt.probe(key);
+                tte.save(
+                    key,
+                    Value(1),
+                    false,
+                    Bound::EXACT,
+                    Depth(1),
+                    None,
+                    Value(1),
+                    generation,
+                );
+            }
+            assert_eq!(tt.hashfull(), 667);
+            // The entries of the old generation aren't counted.
+            tt.new_search();
+            assert_eq!(tt.hashfull(), 0);
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
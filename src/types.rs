@@ -728,18 +728,26 @@ impl Value {
     pub const INFINITE: Value = Value(32601);
     pub const NONE: Value = Value(32602);
 
+    pub fn is_mate_score(self) -> bool {
+        !(Value::MATED_IN_MAX_PLY < self && self < Value::MATE_IN_MAX_PLY)
+    }
+    fn to_centipawns(self) -> i32 {
+        self.0 * 100 / PAWN_VALUE
+    }
+    // negative if we are mated.
+    fn to_mate_plies(self) -> i32 {
+        debug_assert!(self.is_mate_score());
+        if Value::ZERO < self {
+            Value::MATE.0 - self.0 + 1
+        } else {
+            Value::MATED.0 - self.0
+        }
+    }
     pub fn to_usi(self) -> String {
-        if Value::MATED_IN_MAX_PLY < self && self < Value::MATE_IN_MAX_PLY {
-            format!("cp {}", self.0 * 100 / PAWN_VALUE)
+        if self.is_mate_score() {
+            format!("mate {}", self.to_mate_plies())
         } else {
-            format!(
-                "mate {}",
-                if Value::ZERO < self {
-                    Value::MATE.0 - self.0 + 1
-                } else {
-                    Value::MATED.0 - self.0
-                }
-            )
+            format!("cp {}", self.to_centipawns())
         }
     }
     pub fn mate_in(ply: i32) -> Value {
@@ -750,6 +758,16 @@ impl Value {
     }
 }
 
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.is_mate_score() {
+            write!(f, "#{}", self.to_mate_plies())
+        } else {
+            write!(f, "{:+}", self.to_centipawns())
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct PieceType(pub i32);
 
@@ -809,6 +827,17 @@ impl PieceType {
             | (1 << PieceType::DRAGON.0));
         (IS_SLIDER_VAL & (1 << self.0)) != 0
     }
+    // The piece types which move like gold.
+    pub fn is_gold_moving(self) -> bool {
+        matches!(
+            self,
+            PieceType::GOLD
+                | PieceType::PRO_PAWN
+                | PieceType::PRO_LANCE
+                | PieceType::PRO_KNIGHT
+                | PieceType::PRO_SILVER
+        )
+    }
     pub fn is_promotable(self) -> bool {
         match self {
             PieceType::PAWN
@@ -1495,3 +1524,19 @@ fn test_bound() {
     assert!(Bound::EXACT.include_lower());
     assert!(Bound::EXACT.include_upper());
 }
+
+#[test]
+fn test_value_display() {
+    // mate in 3 plies.
+    let v = Value::MATE - Value(2);
+    assert_eq!(v.to_usi(), "mate 3");
+    assert_eq!(v.to_string(), "#3");
+    let v = Value::mated_in(2);
+    assert_eq!(v.to_usi(), "mate -2");
+    assert_eq!(v.to_string(), "#-2");
+    assert_eq!(Value(150 * PAWN_VALUE / 100).to_string(), "+150");
+    assert_eq!(Value(-150 * PAWN_VALUE / 100).to_string(), "-150");
+    assert_eq!(Value::ZERO.to_string(), "+0");
+    assert!(Value::MATE_IN_MAX_PLY.is_mate_score());
+    assert!(!(Value::MATE_IN_MAX_PLY - Value(1)).is_mate_score());
+}
@@ -0,0 +1,113 @@
+// Compact game record. "<start position>.<moves>"
+// start position: "~0" for the start position of the normal game, or sfen with '_' instead of ' '.
+// moves: Move::to_u16() of each move as 3 characters of the URL safe base64 alphabet.
+use crate::movegen::*;
+use crate::position::*;
+use crate::sfen::*;
+use std::sync::atomic::AtomicI64;
+use std::sync::Arc;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+const CHARS_PER_MOVE: usize = 3;
+const START_POSITION_STR: &str = "~0";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum UsenError {
+    InvalidFormat,
+    InvalidStartPosition,
+    IllegalMove { index: usize },
+}
+
+#[allow(dead_code)]
+pub fn game_to_usen(start: &Position, moves: &[Move]) -> String {
+    let sfen = start.to_sfen();
+    let mut s = if sfen == START_SFEN {
+        START_POSITION_STR.to_string()
+    } else {
+        sfen.replace(' ', "_")
+    };
+    s += ".";
+    for m in moves {
+        let v = m.to_u16() as usize;
+        for i in (0..CHARS_PER_MOVE).rev() {
+            s.push(ALPHABET[(v >> (6 * i)) & 0x3f] as char);
+        }
+    }
+    s
+}
+
+#[allow(dead_code)]
+pub fn usen_to_game(usen: &str) -> Result<(Position, Vec<Move>), UsenError> {
+    let mut it = usen.splitn(2, '.');
+    let start_str = it.next().ok_or(UsenError::InvalidFormat)?;
+    let moves_str = it.next().ok_or(UsenError::InvalidFormat)?;
+    let start = if start_str == START_POSITION_STR {
+        Position::new()
+    } else {
+        Position::new_from_sfen(&start_str.replace('_', " "))
+            .map_err(|_| UsenError::InvalidStartPosition)?
+    };
+    if moves_str.len() % CHARS_PER_MOVE != 0 {
+        return Err(UsenError::InvalidFormat);
+    }
+    let mut pos = Position::new_from_position(&start, Arc::new(AtomicI64::new(0)));
+    let mut moves = vec![];
+    for (index, chunk) in moves_str.as_bytes().chunks(CHARS_PER_MOVE).enumerate() {
+        let mut v: usize = 0;
+        for c in chunk {
+            let digit = ALPHABET
+                .iter()
+                .position(|x| x == c)
+                .ok_or(UsenError::InvalidFormat)?;
+            v = (v << 6) | digit;
+        }
+        if v > usize::from(u16::MAX) {
+            return Err(UsenError::IllegalMove { index });
+        }
+        let m = Move::new_from_u16(v as u16, &pos).ok_or(UsenError::IllegalMove { index })?;
+        let gives_check = pos.gives_check(m);
+        pos.do_move(m, gives_check);
+        moves.push(m);
+    }
+    Ok((start, moves))
+}
+
+#[test]
+fn test_usen() {
+    let mut pos = Position::new();
+    let mut moves = vec![];
+    for move_str in &["7g7f", "3c3d", "8h2b+", "3a2b", "B*4e"] {
+        let m = Move::new_from_usi_str(move_str, &pos).unwrap();
+        let gives_check = pos.gives_check(m);
+        pos.do_move(m, gives_check);
+        moves.push(m);
+    }
+    let usen = game_to_usen(&Position::new(), &moves);
+    assert!(usen.starts_with("~0."));
+    assert_eq!(usen.len(), "~0.".len() + moves.len() * CHARS_PER_MOVE);
+    let (start, decoded) = usen_to_game(&usen).unwrap();
+    assert!(start == Position::new());
+    assert_eq!(decoded, moves);
+
+    // not the start position of the normal game.
+    let start = Position::handicap(Handicap::Bishop);
+    let m = Move::new_from_usi_str("3c3d", &start).unwrap();
+    let usen = game_to_usen(&start, &[m]);
+    let (decoded_start, decoded) = usen_to_game(&usen).unwrap();
+    assert!(decoded_start == start);
+    assert_eq!(decoded, [m]);
+
+    assert_eq!(usen_to_game("~0").err(), Some(UsenError::InvalidFormat));
+    assert_eq!(usen_to_game("~0.AB").err(), Some(UsenError::InvalidFormat));
+    assert_eq!(usen_to_game("~0.A!A").err(), Some(UsenError::InvalidFormat));
+    assert_eq!(
+        usen_to_game("x.").err(),
+        Some(UsenError::InvalidStartPosition)
+    );
+    // the same move twice.
+    let usen = game_to_usen(&Position::new(), &[moves[0], moves[0]]);
+    assert_eq!(
+        usen_to_game(&usen).err(),
+        Some(UsenError::IllegalMove { index: 1 })
+    );
+}
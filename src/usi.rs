@@ -86,11 +86,23 @@ fn go(
     Ok(())
 }
 
-fn usi_new_game(thread_pool: &mut ThreadPool, _tt: &mut TranspositionTable) {
+fn usi_new_game(
+    thread_pool: &mut ThreadPool,
+    tt: &mut TranspositionTable,
+    usi_options: &UsiOptions,
+) {
+    thread_pool.wait_for_search_finished();
+    thread_pool.clear();
+    if !usi_options.get_bool("Persistent_Hash") {
+        tt.clear_parallel(usi_options.get_i64("Threads") as usize);
+    }
+}
+
+// Killers live in the search stack, so they don't outlive a search.
+fn gameover(thread_pool: &mut ThreadPool, tt: &mut TranspositionTable) {
     thread_pool.wait_for_search_finished();
     thread_pool.clear();
-    // Is tt.clear() disturbed at the continuous match?
-    //_tt.clear();
+    tt.new_search();
 }
 
 fn self_move(
@@ -128,11 +140,42 @@ fn self_move(
     }
 }
 
-fn position(pos: &mut Position, args: &[&str]) {
+// Returns the moves appended to the previous "position" command, if args only extends it.
+fn appended_moves<'a, 'b>(last_args: &[String], args: &'b [&'a str]) -> Option<&'b [&'a str]> {
+    if last_args.iter().any(|x| x == "moves")
+        && last_args.len() <= args.len()
+        && last_args.iter().zip(args.iter()).all(|(x, y)| x == y)
+    {
+        Some(&args[last_args.len()..])
+    } else {
+        None
+    }
+}
+
+// last_args is the args of the previous "position" command which made pos.
+// If args only appends moves to it, the appended moves are done on pos without reconstruction.
+fn position(pos: &mut Position, last_args: &mut Vec<String>, args: &[&str]) {
+    if let Some(moves) = appended_moves(last_args, args) {
+        for (i, arg) in moves.iter().enumerate() {
+            if let Some(m) = Move::new_from_usi_str(arg, pos) {
+                let gives_check = pos.gives_check(m);
+                pos.do_move(m, gives_check);
+            } else {
+                eprintln!("Invalid move: {}, position: {}", arg, pos.to_sfen());
+                pos.undo_moves(i);
+                return;
+            }
+        }
+        pos.reserve_states();
+        *last_args = args.iter().map(|x| x.to_string()).collect();
+        return;
+    }
+    last_args.clear();
     if args.is_empty() {
         eprintln!(r#"Invalid postion command. expected: "startpos" or "sfen". but found nothing"#,);
         return;
     }
+    let new_args: Vec<String> = args.iter().map(|x| x.to_string()).collect();
     let mut tmp_pos;
     let args = match args[0] {
         "startpos" => {
@@ -181,6 +224,7 @@ fn position(pos: &mut Position, args: &[&str]) {
     }
     *pos = tmp_pos;
     pos.reserve_states();
+    *last_args = new_args;
 }
 
 fn setoption(
@@ -206,7 +250,7 @@ fn setoption(
             }
             let name = args[1];
             let value = args[3];
-            usi_options.set(name, value, thread_pool, tt, ehash);
+            usi_options.set_and_apply(name, value, thread_pool, tt, ehash);
         }
         _ => {
             let mut s = "Error: invalid number of sections.".to_string();
@@ -453,6 +497,22 @@ fn csa_record_to_sfen(csa: &[u8]) -> Result<String, String> {
     Ok(s)
 }
 
+fn debug_command(token: &str, pos: &Position, is_ready: bool) -> Result<String, String> {
+    match token {
+        "d" => Ok(pos.to_string()),
+        "eval" => {
+            if is_ready {
+                Ok(evaluate_static_breakdown(pos))
+            } else {
+                Err(r#"We need "isready" command in advance."#.to_string())
+            }
+        }
+        "key" => Ok(pos.key().0.to_string()),
+        "sfen" => Ok(pos.to_sfen()),
+        _ => Err(format!("unknown command: {}", token)),
+    }
+}
+
 pub fn cmd_loop() {
     let mut tt = Box::new(TranspositionTable::new());
     let mut ehash = Box::new(EvalHash::new());
@@ -461,6 +521,7 @@ pub fn cmd_loop() {
     let mut usi_options = Box::new(UsiOptions::new());
     let mut pos = Box::new(Position::new());
     let mut is_ready = false;
+    let mut last_position_args = vec![];
     loop {
         let cmd = if std::env::args().len() == 1 {
             let mut cmd = String::new();
@@ -483,6 +544,7 @@ pub fn cmd_loop() {
 
         match token {
             // Required commands as USI protocol.
+            "gameover" => gameover(&mut thread_pool, &mut tt),
             "go" => {
                 if is_ready {
                     if let Err(err) = go(&mut thread_pool, &mut tt, &usi_options, &pos, &args[1..])
@@ -514,12 +576,8 @@ pub fn cmd_loop() {
                     println!("readyok");
                 }
             }
-            "ponderhit" => {
-                thread_pool
-                    .ponder
-                    .store(false, std::sync::atomic::Ordering::Relaxed);
-            }
-            "position" => position(&mut pos, &args[1..]),
+            "ponderhit" => thread_pool.ponderhit(),
+            "position" => position(&mut pos, &mut last_position_args, &args[1..]),
             "quit" | "stop" => {
                 thread_pool
                     .stop
@@ -539,19 +597,28 @@ pub fn cmd_loop() {
                 s += "\nusiok";
                 println!("{}", s);
             }
-            "usinewgame" => usi_new_game(&mut thread_pool, &mut tt),
+            "usinewgame" => usi_new_game(&mut thread_pool, &mut tt, &usi_options),
             // Not required commands as USI protocol.
-            "bench_movegen" => bench_movegen(&pos),
-            "d" => pos.print(),
-            "eval" => {
+            "bench" => {
                 if is_ready {
-                    let mut stack = vec![Stack::new(); CURRENT_STACK_INDEX + 1];
-                    println!("{}", evaluate_at_root(&pos, &mut stack).0);
+                    match args.get(1).map_or(Ok(13), |x| x.parse::<u32>()) {
+                        Ok(depth) => {
+                            let result = crate::bench::bench(depth);
+                            println!("nodes: {}", result.total_nodes);
+                            println!("elapsed: {} [msec]", result.elapsed_ms);
+                            println!("nps: {}", result.nps);
+                        }
+                        Err(err) => eprintln!("{}", err),
+                    }
                 } else {
                     eprintln!(r#"We need "isready" command in advance."#);
                 }
             }
-            "key" => println!("{}", pos.key().0),
+            "bench_movegen" => bench_movegen(&pos),
+            "d" | "eval" | "key" | "sfen" => match debug_command(token, &pos, is_ready) {
+                Ok(s) => println!("{}", s),
+                Err(err) => eprintln!("{}", err),
+            },
             "legal_moves" => legal_moves(&pos),
             "self_move" => self_move(&mut thread_pool, &mut tt, &usi_options, &pos),
             "read_csa_dirs_and_output_sfen" => read_csa_dirs_and_output_sfen(&args[1..]),
@@ -593,3 +660,123 @@ fn test_csa_record_to_sfne() {
 
 #[test]
 fn test_usi() {}
+
+#[test]
+fn test_debug_command() {
+    let usi_options = UsiOptions::new();
+    load_evaluate_files(&usi_options.get_string("Eval_Dir")).unwrap();
+    let pos = Position::new();
+    for token in "d\neval\n".lines() {
+        let s = debug_command(token, &pos, true).unwrap();
+        assert!(!s.is_empty());
+    }
+    assert!(debug_command("d", &pos, true).unwrap().contains("key: "));
+    assert!(debug_command("eval", &pos, false).is_err());
+    assert_eq!(debug_command("sfen", &pos, false).unwrap(), pos.to_sfen());
+}
+
+#[test]
+fn test_gameover() {
+    run_search_test(|mut ctx| {
+        ctx.set_threads(1);
+        ctx.search(&Position::new(), LimitsType::with_depth(6));
+        assert!(!ctx.thread_pool.histories_are_cleared());
+        assert!(ctx.thread_pool.nodes_searched() > 0);
+        let generation = ctx.tt.generation();
+
+        gameover(&mut ctx.thread_pool, &mut ctx.tt);
+        assert!(ctx.thread_pool.histories_are_cleared());
+        assert_eq!(ctx.thread_pool.nodes_searched(), 0);
+        assert_ne!(ctx.tt.generation(), generation);
+    });
+}
+
+#[test]
+fn test_clear_hash() {
+    run_search_test(|mut ctx| {
+        ctx.set_threads(1);
+        let fill = |tt: &mut TranspositionTable| {
+            let key = Key(0x1234_5678_9abc_def0);
+            let generation = tt.generation();
+            let (tte, _found) = tt.probe(key);
+            tte.save(
+                key,
+                Value(1),
+                false,
+                Bound::EXACT,
+                Depth(1),
+                None,
+                Value(1),
+                generation,
+            );
+            assert!(!tt.is_cleared());
+        };
+
+        fill(&mut ctx.tt);
+        setoption(
+            &["name", "Clear_Hash"],
+            &mut ctx.usi_options,
+            &mut ctx.thread_pool,
+            &mut ctx.tt,
+            &mut ctx.ehash,
+        );
+        assert!(ctx.tt.is_cleared());
+
+        fill(&mut ctx.tt);
+        usi_new_game(&mut ctx.thread_pool, &mut ctx.tt, &ctx.usi_options);
+        assert!(!ctx.tt.is_cleared());
+
+        setoption(
+            &["name", "Persistent_Hash", "value", "false"],
+            &mut ctx.usi_options,
+            &mut ctx.thread_pool,
+            &mut ctx.tt,
+            &mut ctx.ehash,
+        );
+        usi_new_game(&mut ctx.thread_pool, &mut ctx.tt, &ctx.usi_options);
+        assert!(ctx.tt.is_cleared());
+    });
+}
+
+#[test]
+fn test_position_appends_moves() {
+    let mut pos = Position::new();
+    let mut last_args = vec![];
+    let commands = [
+        "startpos moves",
+        "startpos moves 7g7f",
+        "startpos moves 7g7f 3c3d 2g2f",
+        "startpos moves 7g7f 3c3d 2g2f 4c4d",
+    ];
+    let mut nodes = 0;
+    for (i, command) in commands.iter().enumerate() {
+        let args: Vec<&str> = command.split_whitespace().collect();
+        position(&mut pos, &mut last_args, &args);
+        let mut expected = Position::new();
+        position(&mut expected, &mut vec![], &args);
+        assert_eq!(pos.to_sfen(), expected.to_sfen());
+        assert_eq!(pos.key().0, expected.key().0);
+        // Only the appended moves are done.
+        if i != 0 {
+            nodes += args.len() - commands[i - 1].split_whitespace().count();
+        }
+        assert_eq!(pos.nodes_searched(), nodes as i64);
+    }
+
+    // An illegal appended move leaves the position unchanged.
+    let sfen = pos.to_sfen();
+    let args: Vec<&str> = "startpos moves 7g7f 3c3d 2g2f 4c4d 2f2e 2e2d"
+        .split_whitespace()
+        .collect();
+    position(&mut pos, &mut last_args, &args);
+    assert_eq!(pos.to_sfen(), sfen);
+
+    // The moves don't extend the previous ones, so the position is reconstructed.
+    let args: Vec<&str> = "startpos moves 2g2f".split_whitespace().collect();
+    position(&mut pos, &mut last_args, &args);
+    assert_eq!(pos.nodes_searched(), 1);
+    assert_eq!(
+        pos.to_sfen(),
+        "lnsgkgsnl/1r5b1/ppppppppp/9/9/7P1/PPPPPPP1P/1B5R1/LNSGKGSNL w - 2"
+    );
+}
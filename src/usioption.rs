@@ -18,6 +18,11 @@ enum UsiOptionValue {
         default: bool,
         current: bool,
     },
+    Combo {
+        default: String,
+        current: String,
+        vars: Vec<String>,
+    },
     Button,
 }
 
@@ -42,6 +47,38 @@ impl UsiOptionValue {
             current: default,
         }
     }
+    pub fn combo(default: &str, vars: &[&str]) -> UsiOptionValue {
+        UsiOptionValue::Combo {
+            default: default.to_string(),
+            current: default.to_string(),
+            vars: vars.iter().map(|x| x.to_string()).collect(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum OptionError {
+    IllegalName { name: String },
+    InvalidValue { name: String, value: String },
+    ButtonHasNoValue { name: String },
+}
+
+impl std::fmt::Display for OptionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            OptionError::IllegalName { name } => write!(f, "illegal option name: {}", name),
+            OptionError::InvalidValue { name, value } => {
+                write!(f, r#"invalid value for the option "{}": {}"#, name, value)
+            }
+            OptionError::ButtonHasNoValue { name } => {
+                write!(
+                    f,
+                    r#"The option "{}" is button type and has no value"#,
+                    name
+                )
+            }
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -51,40 +88,60 @@ pub struct UsiOptions {
 
 impl UsiOptions {
     pub fn new() -> UsiOptions {
-        let mut options = std::collections::HashMap::new();
+        let mut options = UsiOptions {
+            v: std::collections::HashMap::new(),
+        };
 
         // The following are all options.
-        options.insert(
-            "Byoyomi_Margin".to_string(),
-            UsiOptionValue::spin(500, 0, i64::max_value()),
-        );
-        options.insert("Clear_Hash".to_string(), UsiOptionValue::Button);
-        options.insert(
-            "Eval_Dir".to_string(),
-            UsiOptionValue::string("eval/20190224"),
-        );
-        options.insert(
-            "Eval_Hash".to_string(),
-            UsiOptionValue::spin(256, 1, 1024 * 1024),
-        );
-        options.insert(
-            "Minimum_Thinking_Time".to_string(),
-            UsiOptionValue::spin(20, 0, 5000),
-        );
-        options.insert("MultiPV".to_string(), UsiOptionValue::spin(1, 1, 500));
-        options.insert("Slow_Mover".to_string(), UsiOptionValue::spin(84, 10, 1000));
-        options.insert("Threads".to_string(), UsiOptionValue::spin(1, 1, 8192));
-        options.insert(
-            "Time_Margin".to_string(),
-            UsiOptionValue::spin(500, 0, i64::max_value()),
-        );
-        options.insert(
-            "USI_Hash".to_string(),
-            UsiOptionValue::spin(256, 1, 1024 * 1024),
-        );
-        options.insert("USI_Ponder".to_string(), UsiOptionValue::check(true));
+        options.add_spin("Aspiration_Delta", 20, 0, 1000);
+        options.add_spin("Byoyomi_Margin", 500, 0, i64::max_value());
+        options.add_spin("Check_Extension_Plies", 1, 0, 2);
+        options.add_button("Clear_Hash");
+        options.add_spin("Contempt", 0, -1000, 1000);
+        options.add_string("Eval_Dir", "eval/20190224");
+        options.add_spin("Eval_Hash", 256, 1, 1024 * 1024);
+        options.add_spin("Futility_Margin_Base", 256, 0, 10000);
+        options.add_spin("Futility_Margin_Per_Depth", 200, 0, 10000);
+        options.add_check("Late_Move_Reduction", true);
+        options.add_spin("Late_Move_Reduction_Scale", 100, 0, 400);
+        options.add_spin("Max_Moves_To_Draw", 0, 0, 100000);
+        options.add_spin("Minimum_Thinking_Time", 20, 0, 5000);
+        options.add_spin("MultiPV", 1, 1, 500);
+        options.add_check("One_Reply_Extension", false);
+        // If false, the transposition table is cleared at "usinewgame".
+        options.add_check("Persistent_Hash", true);
+        options.add_spin("Random_Seed", 0, 0, i64::MAX);
+        options.add_check("Recapture_Extension", false);
+        options.add_spin("Slow_Mover", 84, 10, 1000);
+        options.add_spin("Threads", 1, 1, 8192);
+        options.add_spin("Time_Margin", 500, 0, i64::max_value());
+        options.add_spin("USI_Hash", 256, 1, 1024 * 1024);
+        options.add_check("USI_Ponder", true);
 
-        UsiOptions { v: options }
+        options
+    }
+    // add_*() overwrite the option if it already exists.
+    pub fn add_spin(&mut self, name: &str, default: i64, min: i64, max: i64) {
+        debug_assert!(min <= default && default <= max);
+        self.v
+            .insert(name.to_string(), UsiOptionValue::spin(default, min, max));
+    }
+    pub fn add_check(&mut self, name: &str, default: bool) {
+        self.v
+            .insert(name.to_string(), UsiOptionValue::check(default));
+    }
+    #[allow(dead_code)]
+    pub fn add_combo(&mut self, name: &str, default: &str, vars: &[&str]) {
+        debug_assert!(vars.contains(&default));
+        self.v
+            .insert(name.to_string(), UsiOptionValue::combo(default, vars));
+    }
+    pub fn add_string(&mut self, name: &str, default: &str) {
+        self.v
+            .insert(name.to_string(), UsiOptionValue::string(default));
+    }
+    pub fn add_button(&mut self, name: &str) {
+        self.v.insert(name.to_string(), UsiOptionValue::Button);
     }
     pub fn push_button(&self, key: &str, tt: &mut TranspositionTable) {
         if self.v.get(key).is_none() {
@@ -94,7 +151,7 @@ impl UsiOptions {
         match &self.v[key] {
             UsiOptionValue::Button => match key {
                 "Clear_Hash" => {
-                    tt.clear();
+                    tt.clear_parallel(self.get_i64("Threads") as usize);
                 }
                 _ => unreachable!(),
             },
@@ -104,7 +161,46 @@ impl UsiOptions {
             }
         }
     }
-    pub fn set(
+    // Only stores the value. A spin value out of range is clamped.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), OptionError> {
+        let invalid_value = || OptionError::InvalidValue {
+            name: key.to_string(),
+            value: value.to_string(),
+        };
+        match self.v.get_mut(key) {
+            None => Err(OptionError::IllegalName {
+                name: key.to_string(),
+            }),
+            Some(UsiOptionValue::StringOption { current, .. }) => {
+                *current = value.to_string();
+                Ok(())
+            }
+            Some(UsiOptionValue::Spin {
+                current, min, max, ..
+            }) => {
+                let n = value.parse::<i64>().map_err(|_| invalid_value())?;
+                *current = std::cmp::max(std::cmp::min(n, *max), *min);
+                Ok(())
+            }
+            Some(UsiOptionValue::Check { current, .. }) => {
+                // "true" or "false" is ok. You can only use lowercase.
+                *current = value.parse::<bool>().map_err(|_| invalid_value())?;
+                Ok(())
+            }
+            Some(UsiOptionValue::Combo { current, vars, .. }) => {
+                if !vars.iter().any(|x| x == value) {
+                    return Err(invalid_value());
+                }
+                *current = value.to_string();
+                Ok(())
+            }
+            Some(UsiOptionValue::Button) => Err(OptionError::ButtonHasNoValue {
+                name: key.to_string(),
+            }),
+        }
+    }
+    // "setoption name <key> value <value>". The options which need resizing etc. are applied here.
+    pub fn set_and_apply(
         &mut self,
         key: &str,
         value: &str,
@@ -112,52 +208,21 @@ impl UsiOptions {
         tt: &mut TranspositionTable,
         ehash: &mut EvalHash,
     ) {
-        if self.v.get(key).is_none() {
-            println!("Error: illegal option name: {}", key);
+        if let Err(err) = self.set(key, value) {
+            println!("Error: {}", err);
             return;
         }
-        match self.v[key] {
-            UsiOptionValue::StringOption { .. } => {
-                self.v
-                    .insert(key.to_string(), UsiOptionValue::string(value));
+        match key {
+            "Eval_Hash" => {
+                ehash.resize(self.get_i64(key) as usize, thread_pool);
             }
-            UsiOptionValue::Spin { min, max, .. } => match value.parse::<i64>() {
-                Ok(n) => {
-                    let n = std::cmp::min(n, max);
-                    let n = std::cmp::max(n, min);
-                    self.v
-                        .insert(key.to_string(), UsiOptionValue::spin(n, min, max));
-                    match key {
-                        "Eval_Hash" => {
-                            ehash.resize(n as usize, thread_pool);
-                        }
-                        "Threads" => {
-                            thread_pool.set(n as usize, tt, ehash);
-                        }
-                        "USI_Hash" => {
-                            tt.resize(n as usize, thread_pool);
-                        }
-                        _ => {}
-                    }
-                }
-                Err(err) => {
-                    println!("{:?}", err);
-                    return;
-                }
-            },
-            UsiOptionValue::Check { .. } => {
-                // "true" or "false" is ok. You can only use lowercase.
-                match value.parse::<bool>() {
-                    Ok(b) => {
-                        self.v.insert(key.to_string(), UsiOptionValue::check(b));
-                    }
-                    Err(err) => {
-                        println!("{:?}", err);
-                        return;
-                    }
-                }
+            "Threads" => {
+                thread_pool.set(self.get_i64(key) as usize, tt, ehash);
+            }
+            "USI_Hash" => {
+                tt.resize(self.get_i64(key) as usize, thread_pool);
             }
-            UsiOptionValue::Button => {}
+            _ => {}
         }
     }
     pub fn to_usi_string(&self) -> String {
@@ -177,6 +242,12 @@ impl UsiOptions {
                 UsiOptionValue::Check { default, .. } => {
                     format!("option name {} type check default {}", key, default)
                 }
+                UsiOptionValue::Combo { default, vars, .. } => format!(
+                    "option name {} type combo default {}{}",
+                    key,
+                    default,
+                    vars.iter().fold("".to_string(), |sum, x| sum + " var " + x)
+                ),
                 UsiOptionValue::Button => format!("option name {} type button", key),
             })
             .collect::<Vec<_>>();
@@ -192,6 +263,7 @@ impl UsiOptions {
     pub fn get_string(&self, key: &str) -> String {
         match &self.v[key] {
             UsiOptionValue::StringOption { current, .. } => current.clone(),
+            UsiOptionValue::Combo { current, .. } => current.clone(),
             _ => panic!("Error: illegal option name: {}", key),
         }
     }
@@ -202,3 +274,55 @@ impl UsiOptions {
         }
     }
 }
+
+#[test]
+fn test_usi_options_registration() {
+    let mut options = UsiOptions::new();
+    options.add_spin("Test_Spin", 10, 0, 100);
+    assert!(options
+        .to_usi_string()
+        .lines()
+        .any(|x| x == "option name Test_Spin type spin default 10 min 0 max 100"));
+    assert_eq!(options.set("Test_Spin", "42"), Ok(()));
+    assert_eq!(options.get_i64("Test_Spin"), 42);
+    assert_eq!(options.set("Test_Spin", "101"), Ok(()));
+    assert_eq!(options.get_i64("Test_Spin"), 100);
+    assert_eq!(options.set("Test_Spin", "-1"), Ok(()));
+    assert_eq!(options.get_i64("Test_Spin"), 0);
+    assert_eq!(
+        options.set("Test_Spin", "abc"),
+        Err(OptionError::InvalidValue {
+            name: "Test_Spin".to_string(),
+            value: "abc".to_string()
+        })
+    );
+    assert_eq!(options.get_i64("Test_Spin"), 0);
+    // The default isn't changed by set().
+    assert!(options
+        .to_usi_string()
+        .lines()
+        .any(|x| x == "option name Test_Spin type spin default 10 min 0 max 100"));
+
+    options.add_combo("Test_Combo", "b", &["a", "b"]);
+    assert!(options
+        .to_usi_string()
+        .lines()
+        .any(|x| x == "option name Test_Combo type combo default b var a var b"));
+    assert_eq!(options.set("Test_Combo", "a"), Ok(()));
+    assert_eq!(options.get_string("Test_Combo"), "a");
+    assert!(options.set("Test_Combo", "c").is_err());
+
+    options.add_button("Test_Button");
+    assert_eq!(
+        options.set("Test_Button", "true"),
+        Err(OptionError::ButtonHasNoValue {
+            name: "Test_Button".to_string()
+        })
+    );
+    assert_eq!(
+        options.set("No_Such_Option", "1"),
+        Err(OptionError::IllegalName {
+            name: "No_Such_Option".to_string()
+        })
+    );
+}